@@ -0,0 +1,375 @@
+//! Directories downloaded as `?download=zip` or `?download=tar.gz`, streamed while
+//! they are walked.
+
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{
+    write::{DeflateEncoder, GzEncoder},
+    Compression, Crc,
+};
+use tokio::io::{AsyncWrite, AsyncWriteExt as _};
+
+use crate::{
+    fs::File as _,
+    headers,
+    http::{percent_encode, Request, Response},
+    listing, Context, FileSystemInterface,
+};
+
+/// Output is written to the connection whenever this much is pending.
+const CHUNK: usize = 64 * 1024;
+
+/// Most entries in a tar.gz download. Symlinked directories can form loops, so the
+/// walk stops here even if the files are empty.
+const MAX_ENTRIES: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    TarGz,
+}
+
+impl Format {
+    /// The format requested with `?download=`.
+    pub fn requested(request: &Request) -> Option<Self> {
+        let (_, value) = request
+            .query_pairs()
+            .into_iter()
+            .find(|(key, _)| key == "download")?;
+        match value.as_str() {
+            "zip" => Some(Self::Zip),
+            "tar.gz" | "tgz" => Some(Self::TarGz),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+        }
+    }
+
+    fn mime(self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::TarGz => "application/gzip",
+        }
+    }
+}
+
+/// A file or folder in the archive.
+struct Item {
+    /// `/`-separated path inside the archive.
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+    modified: Option<SystemTime>,
+}
+
+/// Streams `dir` as an archive, or answers with an error if it exceeds
+/// [`crate::Options::archive_limit`].
+pub async fn serve(
+    stream: &mut (impl AsyncWrite + Unpin),
+    request: &Request,
+    dir: &Path,
+    format: Format,
+    ctx: &Context<impl FileSystemInterface>,
+) -> std::io::Result<()> {
+    // without ZIP64, offsets and the entry count have to fit into the headers
+    let (limit, max_entries) = match format {
+        Format::Zip => (
+            ctx.options.archive_limit.min(u32::MAX as u64 / 2),
+            u16::MAX as usize - 1,
+        ),
+        Format::TarGz => (ctx.options.archive_limit, MAX_ENTRIES),
+    };
+    let items = match walk(&ctx.fs, dir, limit, max_entries).await {
+        Ok(Some(v)) => v,
+        Ok(None) => {
+            let message = format!(
+                "The directory holds more than {max_entries} entries or {limit} bytes, the archive limit.\n"
+            );
+            let mut response = Response::ok("text/plain", message.into_bytes());
+            response.status = "403 Forbidden";
+            headers::apply(&ctx.options, request, &mut response);
+            return response.write(stream, false).await;
+        }
+        Err(_) => {
+            let mut response = Response::new("500 INTERNAL SERVER ERROR");
+            headers::apply(&ctx.options, request, &mut response);
+            return response.write(stream, false).await;
+        }
+    };
+
+    let name = dir
+        .file_name()
+        .map(|v| v.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "archive".to_string());
+    let file_name = format!("{name}.{}", format.extension());
+    let mut response = Response::new("200 OK")
+        .with_header("Content-Type", format.mime())
+        .with_header(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+                file_name.replace(|c: char| c == '"' || !c.is_ascii(), "_"),
+                percent_encode(&file_name)
+            ),
+        )
+        .with_header("Cache-Control", "no-cache");
+    headers::apply(&ctx.options, request, &mut response);
+    response.write_head(stream).await?;
+
+    match format {
+        Format::Zip => write_zip(stream, &items, ctx).await,
+        Format::TarGz => write_tar_gz(stream, &items, ctx).await,
+    }
+}
+
+/// All files and folders below `root`, parents before their children, or `None` once
+/// they exceed `limit` bytes or `max_entries`.
+async fn walk(
+    fs: &impl FileSystemInterface,
+    root: &Path,
+    limit: u64,
+    max_entries: usize,
+) -> crate::Result<Option<Vec<Item>>> {
+    let mut items = vec![];
+    let mut total = 0u64;
+    let mut pending = vec![(String::new(), root.to_path_buf())];
+    while let Some((prefix, dir)) = pending.pop() {
+        let mut entries = listing::read(fs, &dir).await?;
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        for entry in entries {
+            let name = format!("{prefix}{}", entry.name);
+            let path = dir.join(&entry.name);
+            if !entry.is_dir {
+                total = total.saturating_add(entry.size);
+            }
+            if total > limit || items.len() >= max_entries {
+                return Ok(None);
+            }
+            if entry.is_dir {
+                pending.push((format!("{name}/"), path.clone()));
+            }
+            items.push(Item {
+                name,
+                path,
+                is_dir: entry.is_dir,
+                modified: entry.modified,
+            });
+        }
+    }
+    Ok(Some(items))
+}
+
+async fn write_zip(
+    stream: &mut (impl AsyncWrite + Unpin),
+    items: &[Item],
+    ctx: &Context<impl FileSystemInterface>,
+) -> std::io::Result<()> {
+    let mut central = vec![];
+    let mut offset = 0u32;
+    for item in items {
+        let (time, date) = dos_time(item.modified);
+        let name = match item.is_dir {
+            true => format!("{}/", item.name),
+            false => item.name.clone(),
+        };
+        let (method, crc, size, data) = match item.is_dir {
+            true => (0u16, 0, 0, vec![]),
+            false => {
+                let contents = read(ctx, &item.path).await;
+                let mut crc = Crc::new();
+                crc.update(&contents);
+                let mut encoder = DeflateEncoder::new(vec![], Compression::fast());
+                encoder.write_all(&contents)?;
+                (8, crc.sum(), fit::<u32>(contents.len())?, encoder.finish()?)
+            }
+        };
+        let mut header = vec![];
+        // local file header, flag 0x0800 marks UTF-8 names
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes());
+        header.extend_from_slice(&0x0800u16.to_le_bytes());
+        header.extend_from_slice(&method.to_le_bytes());
+        header.extend_from_slice(&time.to_le_bytes());
+        header.extend_from_slice(&date.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&fit::<u32>(data.len())?.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&fit::<u16>(name.len())?.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+
+        // central directory entry, made by unix to keep the permissions
+        let mode: u32 = if item.is_dir { 0o40755 } else { 0o100644 };
+        central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        central.extend_from_slice(&(3u16 << 8 | 20).to_le_bytes());
+        central.extend_from_slice(&header[4..30]);
+        // comment length, disk number and internal attributes
+        central.extend_from_slice(&[0; 6]);
+        central.extend_from_slice(&(mode << 16).to_le_bytes());
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+
+        // files may have grown since the walk, and deflate may expand them
+        offset = offset
+            .checked_add(fit(header.len() + data.len())?)
+            .ok_or_else(too_large)?;
+        stream.write_all(&header).await?;
+        stream.write_all(&data).await?;
+    }
+    let mut end = vec![];
+    end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    end.extend_from_slice(&[0; 4]);
+    let count: u16 = fit(items.len())?;
+    end.extend_from_slice(&count.to_le_bytes());
+    end.extend_from_slice(&count.to_le_bytes());
+    end.extend_from_slice(&fit::<u32>(central.len())?.to_le_bytes());
+    end.extend_from_slice(&offset.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes());
+    stream.write_all(&central).await?;
+    stream.write_all(&end).await?;
+    stream.flush().await
+}
+
+async fn write_tar_gz(
+    stream: &mut (impl AsyncWrite + Unpin),
+    items: &[Item],
+    ctx: &Context<impl FileSystemInterface>,
+) -> std::io::Result<()> {
+    let mut encoder = GzEncoder::new(vec![], Compression::fast());
+    for item in items {
+        let mtime = item
+            .modified
+            .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+            .map(|v| v.as_secs())
+            .unwrap_or_default();
+        match item.is_dir {
+            true => encoder.write_all(&tar_header(&format!("{}/", item.name), 0, mtime, b'5'))?,
+            false => {
+                let contents = read(ctx, &item.path).await;
+                let size = contents.len() as u64;
+                encoder.write_all(&tar_header(&item.name, size, mtime, b'0'))?;
+                for chunk in contents.chunks(CHUNK) {
+                    encoder.write_all(chunk)?;
+                    if encoder.get_ref().len() >= CHUNK {
+                        stream.write_all(&std::mem::take(encoder.get_mut())).await?;
+                    }
+                }
+                encoder.write_all(&vec![0; padding(size)])?;
+            }
+        }
+        stream.write_all(&std::mem::take(encoder.get_mut())).await?;
+    }
+    encoder.write_all(&[0; 1024])?;
+    stream.write_all(&encoder.finish()?).await?;
+    stream.flush().await
+}
+
+async fn read(ctx: &Context<impl FileSystemInterface>, path: &Path) -> Vec<u8> {
+    match ctx.fs.get_file(path).await {
+        Ok(mut file) => file.read_to_end().await,
+        Err(_) => vec![],
+    }
+}
+
+/// A ustar header, preceded by a GNU long name entry for names over 100 bytes.
+fn tar_header(name: &str, size: u64, mtime: u64, kind: u8) -> Vec<u8> {
+    let mut out = vec![];
+    if name.len() > 100 {
+        let long = name.len() as u64 + 1;
+        out.extend(tar_header("././@LongLink", long, 0, b'L'));
+        out.extend_from_slice(name.as_bytes());
+        out.resize(out.len() + 1 + padding(long), 0);
+    }
+    let mut header = [0u8; 512];
+    let mut put = |at: usize, value: &[u8]| header[at..at + value.len()].copy_from_slice(value);
+    put(0, &name.as_bytes()[..name.len().min(100)]);
+    put(
+        100,
+        if kind == b'5' {
+            b"0000755\0"
+        } else {
+            b"0000644\0"
+        },
+    );
+    put(108, b"0000000\0");
+    put(116, b"0000000\0");
+    // GNU base-256 for sizes that don't fit 11 octal digits
+    match size < 1 << 33 {
+        true => put(124, format!("{size:011o}\0").as_bytes()),
+        false => {
+            put(124, &[0x80, 0, 0, 0]);
+            put(128, &size.to_be_bytes());
+        }
+    }
+    put(136, format!("{mtime:011o}\0").as_bytes());
+    put(148, b"        ");
+    put(156, &[kind]);
+    put(257, b"ustar\0");
+    put(263, b"00");
+    let checksum: u32 = header.iter().map(|v| *v as u32).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    out.extend_from_slice(&header);
+    out
+}
+
+/// `value` as a narrower ZIP field, or an error that aborts the download.
+fn fit<T: TryFrom<usize>>(value: usize) -> std::io::Result<T> {
+    T::try_from(value).map_err(|_| too_large())
+}
+
+fn too_large() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "the archive outgrew the ZIP format",
+    )
+}
+
+/// Zeros after `size` bytes of data up to the next 512 byte block.
+fn padding(size: u64) -> usize {
+    ((512 - size % 512) % 512) as usize
+}
+
+/// MS-DOS time and date, which can't express anything before 1980.
+fn dos_time(modified: Option<SystemTime>) -> (u16, u16) {
+    let (year, month, day, secs) = listing::civil(modified.unwrap_or(UNIX_EPOCH));
+    if year < 1980 {
+        return (0, 1 << 5 | 1);
+    }
+    let time = (secs / 3600) << 11 | (secs % 3600 / 60) << 5 | (secs % 60 / 2);
+    let date = (year - 1980) << 9 | month << 5 | day;
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tar_sizes() {
+        let header = tar_header("a.txt", 0o1234, 0, b'0');
+        assert_eq!(&header[124..136], b"00000001234\0");
+        let huge = 9 << 30;
+        let header = tar_header("a.txt", huge, 0, b'0');
+        assert_eq!(header[124], 0x80);
+        assert_eq!(&header[128..136], &huge.to_be_bytes());
+        let long = tar_header(&"x".repeat(150), 1, 0, b'0');
+        assert_eq!(long.len(), 3 * 512);
+        assert_eq!(long[156], b'L');
+    }
+
+    #[test]
+    fn zip_fields_are_checked() {
+        assert_eq!(fit::<u16>(65535).unwrap(), u16::MAX);
+        assert!(fit::<u16>(65536).is_err());
+        assert!(fit::<u32>(u32::MAX as usize + 1).is_err());
+    }
+}
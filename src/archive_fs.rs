@@ -0,0 +1,717 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Read as _},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{
+    read::{DeflateDecoder, GzDecoder},
+    Crc,
+};
+
+use crate::{
+    memory::{not_found, relative},
+    Dir, Error, File, FileSystemInterface, Kind, Metadata, Signal,
+};
+
+/// How often the archive is checked for a replacement.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// The largest `.tar` a `.tar.gz` may decompress to, since it is kept in memory.
+const MAX_TAR_LEN: u64 = 1 << 30;
+
+/// Serves the contents of a `.zip`, `.tar` or `.tar.gz` file without extracting it,
+/// e.g. build artifacts from CI.
+///
+/// Entries are indexed when the archive is opened and decompressed when requested. If
+/// everything is inside one folder, that folder is served. When the archive file is
+/// replaced, it is indexed again and the browsers showing changed files reload.
+#[derive(Clone)]
+pub struct ArchiveFileSystem {
+    inner: Arc<RwLock<Index>>,
+}
+
+struct Index {
+    /// The archive file, which is also the served path.
+    path: PathBuf,
+    /// Modification time and size of the indexed archive file.
+    stamp: (Option<SystemTime>, u64),
+    /// The archive, decompressed as a whole for `.tar.gz`.
+    data: Arc<Vec<u8>>,
+    /// Files and directories by relative path, except the root directory.
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+#[derive(Clone)]
+enum Entry {
+    File {
+        /// Position of the stored data in [`Index::data`].
+        offset: usize,
+        stored: usize,
+        deflated: bool,
+        len: u64,
+        crc: u32,
+        modified: Option<SystemTime>,
+    },
+    Dir {
+        modified: Option<SystemTime>,
+    },
+}
+
+impl ArchiveFileSystem {
+    /// Indexes the archive at `path`, which is passed to [`crate::serve`] as the path
+    /// to serve.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let index = Index::open(path.into())?;
+        Ok(Self {
+            inner: Arc::new(RwLock::new(index)),
+        })
+    }
+
+    /// The path of `path` inside the archive, e.g. `index.html` for `site.zip/index.html`.
+    fn resolve(&self, path: &Path) -> crate::Result<PathBuf> {
+        let index = self.inner.read().unwrap();
+        path.strip_prefix(&index.path)
+            .ok()
+            .and_then(relative)
+            .ok_or_else(|| Error::Io(not_found()))
+    }
+
+    /// Indexes the archive again if the file changed, and notifies the browsers.
+    async fn reload(&self, signal: &Signal) {
+        let (path, stamp) = {
+            let index = self.inner.read().unwrap();
+            (index.path.clone(), index.stamp)
+        };
+        // missing while it is being replaced
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            return;
+        };
+        if (metadata.modified().ok(), metadata.len()) == stamp {
+            return;
+        }
+        let new = match tokio::task::spawn_blocking(move || Index::open(path)).await {
+            Ok(Ok(v)) => v,
+            Ok(Err(_e)) => {
+                #[cfg(feature = "log")]
+                log::warn!("Failed to read the replaced archive: {_e}");
+                // retried once the file changes again
+                self.inner.write().unwrap().stamp = (metadata.modified().ok(), metadata.len());
+                return;
+            }
+            Err(_) => return,
+        };
+        let old = std::mem::replace(&mut *self.inner.write().unwrap(), new);
+        let index = self.inner.read().unwrap();
+        let mut dirs = BTreeSet::new();
+        for (path, entry) in &index.entries {
+            match (old.entries.get(path), entry) {
+                (None, _) => {
+                    dirs.insert(parent(path));
+                }
+                (Some(Entry::File { len, crc, .. }), Entry::File { len: l, crc: c, .. })
+                    if len == l && crc == c => {}
+                (Some(Entry::Dir { .. }), Entry::Dir { .. }) => {}
+                (Some(_), _) => signal.send_signal(path.clone()),
+            }
+        }
+        for path in old.entries.keys() {
+            if !index.entries.contains_key(path) {
+                dirs.insert(parent(path));
+            }
+        }
+        for dir in dirs {
+            signal.send_dir_changed(dir);
+        }
+    }
+}
+
+impl Index {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let metadata = std::fs::metadata(&path)?;
+        let stamp = (metadata.modified().ok(), metadata.len());
+        let mut data = std::fs::read(&path)?;
+        let entries = match data.as_slice() {
+            [0x50, 0x4b, ..] => read_zip(&data)?,
+            [0x1f, 0x8b, ..] => {
+                data = gunzip(&data, MAX_TAR_LEN)?;
+                read_tar(&data)?
+            }
+            _ => read_tar(&data)?,
+        };
+
+        let mut entries: Vec<_> = entries
+            .into_iter()
+            .filter_map(|(name, entry)| Some((relative(Path::new(&name))?, entry)))
+            .filter(|(path, _)| !path.starts_with("__MACOSX") && !path.as_os_str().is_empty())
+            .collect();
+        // serve `dist/` of an archive that only contains `dist/`
+        let first = |v: &Path| v.components().next().map(|v| v.as_os_str().to_owned());
+        let top = entries.first().and_then(|(path, _)| first(path));
+        let single = entries.iter().all(|(path, entry)| {
+            first(path) == top
+                && (path.components().count() > 1 || matches!(entry, Entry::Dir { .. }))
+        });
+        if let (true, Some(top)) = (single, top) {
+            entries = entries
+                .into_iter()
+                .filter_map(|(path, entry)| {
+                    Some((path.strip_prefix(&top).ok()?.to_path_buf(), entry))
+                })
+                .filter(|(path, _)| !path.as_os_str().is_empty())
+                .collect();
+        }
+
+        let mut index = BTreeMap::new();
+        for (path, entry) in entries {
+            for dir in path.ancestors().skip(1) {
+                if !dir.as_os_str().is_empty() {
+                    index
+                        .entry(dir.to_path_buf())
+                        .or_insert(Entry::Dir { modified: None });
+                }
+            }
+            index.insert(path, entry);
+        }
+        Ok(Self {
+            path,
+            stamp,
+            data: Arc::new(data),
+            entries: index,
+        })
+    }
+
+    fn kind(&self, path: &Path) -> Option<Kind> {
+        match self.entries.get(path) {
+            Some(Entry::File { .. }) => Some(Kind::File),
+            Some(Entry::Dir { .. }) => Some(Kind::Dir),
+            None if path.as_os_str().is_empty() => Some(Kind::Dir),
+            None => None,
+        }
+    }
+}
+
+/// Entries of a ZIP file from its central directory. ZIP64 isn't supported.
+fn read_zip(data: &[u8]) -> io::Result<Vec<(String, Entry)>> {
+    // the end record is followed by a comment of up to 64 KiB
+    let end = (0..=data.len().saturating_sub(22))
+        .rev()
+        .take(u16::MAX as usize + 1)
+        .find(|&i| data[i..].starts_with(&[0x50, 0x4b, 5, 6]))
+        .ok_or_else(|| invalid("the ZIP file has no central directory"))?;
+    let count = u16_at(data, end + 10)?;
+    let mut at = u32_at(data, end + 16)? as usize;
+    let mut entries = vec![];
+    for _ in 0..count {
+        if u32_at(data, at)? != 0x02014b50 {
+            return Err(invalid("broken ZIP central directory"));
+        }
+        let method = u16_at(data, at + 10)?;
+        let modified = from_dos(u16_at(data, at + 12)?, u16_at(data, at + 14)?);
+        let crc = u32_at(data, at + 16)?;
+        let stored = u32_at(data, at + 20)?;
+        let len = u32_at(data, at + 24)?;
+        let name_len = u16_at(data, at + 28)? as usize;
+        let extra_len = u16_at(data, at + 30)? as usize;
+        let comment_len = u16_at(data, at + 32)? as usize;
+        let local = u32_at(data, at + 42)?;
+        if [stored, len, local].contains(&u32::MAX) {
+            return Err(invalid("ZIP64 archives aren't supported"));
+        }
+        let name = data
+            .get(at + 46..at + 46 + name_len)
+            .ok_or_else(|| invalid("truncated ZIP file"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        at += 46 + name_len + extra_len + comment_len;
+
+        if name.ends_with('/') {
+            entries.push((name, Entry::Dir { modified }));
+            continue;
+        }
+        // e.g. bzip2 or encrypted entries
+        if method != 0 && method != 8 {
+            continue;
+        }
+        // the local header may have other extra fields than the central one
+        let local = local as usize;
+        let offset =
+            local + 30 + u16_at(data, local + 26)? as usize + u16_at(data, local + 28)? as usize;
+        if offset + stored as usize > data.len() {
+            return Err(invalid("truncated ZIP file"));
+        }
+        let entry = Entry::File {
+            offset,
+            stored: stored as usize,
+            deflated: method == 8,
+            len: len as u64,
+            crc,
+            modified,
+        };
+        entries.push((name, entry));
+    }
+    Ok(entries)
+}
+
+/// Entries of an uncompressed tar file, with GNU and pax long names. Links and other
+/// special files are skipped.
+fn read_tar(data: &[u8]) -> io::Result<Vec<(String, Entry)>> {
+    if data.len() < 512 {
+        return Err(invalid("not a ZIP or tar archive"));
+    }
+    let mut entries = vec![];
+    let mut long_name: Option<String> = None;
+    let mut at = 0;
+    while let Some(header) = data.get(at..at + 512) {
+        if header.iter().all(|v| *v == 0) {
+            break;
+        }
+        let checksum: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                if (148..156).contains(&i) {
+                    32
+                } else {
+                    *v as u64
+                }
+            })
+            .sum();
+        if octal(&header[148..156]) != Some(checksum) {
+            return Err(invalid("not a ZIP or tar archive"));
+        }
+        let len = octal(&header[124..136]).ok_or_else(|| invalid("broken tar header"))?;
+        let modified = octal(&header[136..148]).map(|v| UNIX_EPOCH + Duration::from_secs(v));
+        let offset = at + 512;
+        let contents = data
+            .get(offset..offset + len as usize)
+            .ok_or_else(|| invalid("truncated tar file"))?;
+        at = offset + (len as usize).div_ceil(512) * 512;
+
+        let name = long_name.take().unwrap_or_else(|| {
+            let name = text(&header[..100]);
+            match (&header[257..262] == b"ustar", text(&header[345..500])) {
+                (true, prefix) if !prefix.is_empty() => format!("{prefix}/{name}"),
+                _ => name,
+            }
+        });
+        match header[156] {
+            b'L' => long_name = Some(text(contents)),
+            b'x' => long_name = pax_path(contents),
+            b'0' | b'7' | 0 => {
+                let mut crc = Crc::new();
+                crc.update(contents);
+                let entry = Entry::File {
+                    offset,
+                    stored: len as usize,
+                    deflated: false,
+                    len,
+                    crc: crc.sum(),
+                    modified,
+                };
+                entries.push((name, entry));
+            }
+            b'5' => entries.push((name, Entry::Dir { modified })),
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+/// The `path` record of a pax extended header, made of `<length> <key>=<value>\n`.
+fn pax_path(mut records: &[u8]) -> Option<String> {
+    while let Some(space) = records.iter().position(|v| *v == b' ') {
+        let len: usize = std::str::from_utf8(&records[..space]).ok()?.parse().ok()?;
+        let record = records.get(space + 1..len)?;
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(String::from_utf8_lossy(path.strip_suffix(b"\n")?).into_owned());
+        }
+        records = &records[len..];
+    }
+    None
+}
+
+/// A NUL-terminated string field of a tar header.
+fn text(field: &[u8]) -> String {
+    let end = field.iter().position(|v| *v == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// An octal number field of a tar header.
+fn octal(field: &[u8]) -> Option<u64> {
+    let text = std::str::from_utf8(field).ok()?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    match text.is_empty() {
+        true => Some(0),
+        false => u64::from_str_radix(text, 8).ok(),
+    }
+}
+
+fn u16_at(data: &[u8], at: usize) -> io::Result<u16> {
+    let bytes = data
+        .get(at..at + 2)
+        .ok_or_else(|| invalid("truncated ZIP file"))?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], at: usize) -> io::Result<u32> {
+    let bytes = data
+        .get(at..at + 4)
+        .ok_or_else(|| invalid("truncated ZIP file"))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// MS-DOS time and date, taken as UTC like [`crate::archive`] writes them.
+fn from_dos(time: u16, date: u16) -> Option<SystemTime> {
+    let (year, month, day) = (
+        1980 + (date >> 9) as i64,
+        (date >> 5 & 15) as i64,
+        (date & 31) as i64,
+    );
+    if !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    // days since 1970-01-01 from the civil date, by Howard Hinnant
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let days = era * 146097 + yoe * 365 + yoe / 4 - yoe / 100 + doy - 719468;
+    let secs = (time >> 11) as u64 * 3600 + (time >> 5 & 63) as u64 * 60 + (time & 31) as u64 * 2;
+    Some(UNIX_EPOCH + Duration::from_secs(days as u64 * 86400 + secs))
+}
+
+fn parent(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new("")).to_path_buf()
+}
+
+/// Decompresses `data`, failing if it is longer than `limit`.
+fn gunzip(data: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+    let mut tar = vec![];
+    GzDecoder::new(data).take(limit + 1).read_to_end(&mut tar)?;
+    match tar.len() as u64 > limit {
+        true => Err(invalid("the decompressed archive is too large")),
+        false => Ok(tar),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+struct ArchiveDir {
+    entries: std::vec::IntoIter<PathBuf>,
+}
+
+impl Dir for ArchiveDir {
+    async fn get_next(&mut self) -> crate::Result<Option<PathBuf>> {
+        Ok(self.entries.next())
+    }
+}
+
+struct ArchiveFile {
+    contents: Vec<u8>,
+}
+
+impl File for ArchiveFile {
+    async fn read_to_end(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.contents)
+    }
+}
+
+impl FileSystemInterface for ArchiveFileSystem {
+    async fn get_file(&self, path: &Path) -> crate::Result<impl File> {
+        let path = self.resolve(path)?;
+        let (data, entry) = {
+            let index = self.inner.read().unwrap();
+            (index.data.clone(), index.entries.get(&path).cloned())
+        };
+        let Some(Entry::File {
+            offset,
+            stored,
+            deflated,
+            len,
+            ..
+        }) = entry
+        else {
+            return Err(Error::Io(not_found()));
+        };
+        let raw = &data[offset..offset + stored];
+        let contents = match deflated {
+            true => {
+                // the header's size isn't trusted for allocating, only as a limit
+                let mut contents = vec![];
+                DeflateDecoder::new(raw)
+                    .take(len)
+                    .read_to_end(&mut contents)?;
+                contents
+            }
+            false => raw.to_vec(),
+        };
+        Ok(ArchiveFile { contents })
+    }
+
+    async fn get_dir(&self, path: &Path) -> crate::Result<impl Dir> {
+        let dir = self.resolve(path)?;
+        let index = self.inner.read().unwrap();
+        if index.kind(&dir) != Some(Kind::Dir) {
+            return Err(Error::Io(not_found()));
+        }
+        let entries: Vec<_> = index
+            .entries
+            .keys()
+            .filter(|v| v.parent() == Some(&dir))
+            .map(|v| index.path.join(v))
+            .collect();
+        Ok(ArchiveDir {
+            entries: entries.into_iter(),
+        })
+    }
+
+    async fn metadata(&self, path: &Path) -> crate::Result<Metadata> {
+        let path = self.resolve(path)?;
+        let index = self.inner.read().unwrap();
+        let metadata = match index.entries.get(&path) {
+            Some(Entry::File { len, modified, .. }) => Metadata {
+                kind: Kind::File,
+                len: *len,
+                modified: *modified,
+            },
+            Some(Entry::Dir { modified }) => Metadata {
+                kind: Kind::Dir,
+                len: 0,
+                modified: *modified,
+            },
+            None if path.as_os_str().is_empty() => Metadata {
+                kind: Kind::Dir,
+                len: 0,
+                modified: index.stamp.0,
+            },
+            None => return Err(Error::Io(not_found())),
+        };
+        Ok(metadata)
+    }
+
+    fn report_changes(&self, signal: Arc<Signal>) -> bool {
+        let fs = self.clone();
+        // stops once the server is gone, e.g. when it failed to start
+        let signal = Arc::downgrade(&signal);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(signal) = signal.upgrade() else {
+                    break;
+                };
+                fs.reload(&signal).await;
+            }
+        });
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder},
+        Compression,
+    };
+
+    use super::*;
+
+    /// A ZIP file of `(name, method, stored data, uncompressed length)` entries.
+    fn zip(entries: &[(&str, u16, &[u8], u32)]) -> Vec<u8> {
+        let (mut data, mut central) = (vec![], vec![]);
+        for (name, method, stored, len) in entries {
+            let local = data.len() as u32;
+            data.extend_from_slice(&[0x50, 0x4b, 3, 4, 20, 0, 0, 0]);
+            data.extend_from_slice(&method.to_le_bytes());
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+            data.extend_from_slice(&len.to_le_bytes());
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(&[0, 0]);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(stored);
+
+            central.extend_from_slice(&[0x50, 0x4b, 1, 2, 20, 0, 20, 0, 0, 0]);
+            central.extend_from_slice(&method.to_le_bytes());
+            // 2024-01-01 00:00:00
+            central.extend_from_slice(&[0, 0, 0x21, 0x58, 0, 0, 0, 0]);
+            central.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+            central.extend_from_slice(&len.to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&local.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let start = data.len() as u32;
+        let count = entries.len() as u16;
+        data.extend_from_slice(&central);
+        data.extend_from_slice(&[0x50, 0x4b, 5, 6, 0, 0, 0, 0]);
+        data.extend_from_slice(&count.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        data.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        data.extend_from_slice(&start.to_le_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data
+    }
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// A tar header block of `kind` for `name` with `len` bytes of contents.
+    fn tar_header(name: &str, kind: u8, len: usize) -> Vec<u8> {
+        let mut header = vec![0; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{len:011o}").as_bytes());
+        header[136..147].copy_from_slice(b"00000000000");
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[148..156].fill(b' ');
+        let checksum: u32 = header.iter().map(|v| *v as u32).sum();
+        header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+        header
+    }
+
+    fn tar_entry(tar: &mut Vec<u8>, name: &str, kind: u8, contents: &[u8]) {
+        tar.extend_from_slice(&tar_header(name, kind, contents.len()));
+        tar.extend_from_slice(contents);
+        tar.resize(tar.len().div_ceil(512) * 512, 0);
+    }
+
+    fn names(entries: &[(String, Entry)]) -> Vec<&str> {
+        entries.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn zip_entries() {
+        let text = b"hello hello hello";
+        let data = zip(&[
+            ("site/", 0, b"", 0),
+            ("site/a.txt", 0, b"stored", 6),
+            ("site/b.txt", 8, &deflate(text), text.len() as u32),
+            ("site/c.bz2", 12, b"??", 2),
+        ]);
+        let entries = read_zip(&data).unwrap();
+        assert_eq!(names(&entries), ["site/", "site/a.txt", "site/b.txt"]);
+        let Entry::File {
+            offset,
+            stored,
+            deflated,
+            len,
+            modified,
+            ..
+        } = &entries[1].1
+        else {
+            panic!("not a file");
+        };
+        assert_eq!(&data[*offset..offset + stored], b"stored");
+        assert_eq!((*deflated, *len), (false, 6));
+        assert_eq!(
+            *modified,
+            Some(UNIX_EPOCH + Duration::from_secs(1704067200))
+        );
+        assert!(matches!(
+            entries[2].1,
+            Entry::File {
+                deflated: true,
+                len: 17,
+                ..
+            }
+        ));
+
+        assert!(read_zip(&data[..data.len() - 30]).is_err());
+        assert!(read_zip(b"PK not a zip").is_err());
+    }
+
+    #[test]
+    fn tar_entries() {
+        let mut tar = vec![];
+        tar_entry(&mut tar, "site/", b'5', b"");
+        tar_entry(&mut tar, "site/a.txt", b'0', b"contents");
+        tar_entry(&mut tar, "././@LongLink", b'L', b"site/long name.txt\0");
+        tar_entry(&mut tar, "site/long", b'0', b"long");
+        tar_entry(&mut tar, "PaxHeader", b'x', b"26 path=site/pax name.txt\n");
+        tar_entry(&mut tar, "site/pax", b'0', b"pax");
+        tar_entry(&mut tar, "site/link", b'2', b"");
+        tar.extend_from_slice(&[0; 1024]);
+
+        let entries = read_tar(&tar).unwrap();
+        assert_eq!(
+            names(&entries),
+            [
+                "site/",
+                "site/a.txt",
+                "site/long name.txt",
+                "site/pax name.txt"
+            ]
+        );
+        let Entry::File { offset, len, .. } = &entries[1].1 else {
+            panic!("not a file");
+        };
+        assert_eq!(&tar[*offset..offset + *len as usize], b"contents");
+
+        let mut broken = tar.clone();
+        broken[0] = b'x';
+        assert!(read_tar(&broken).is_err());
+        assert!(read_tar(&tar[..1028]).is_err());
+        assert!(read_tar(b"short").is_err());
+    }
+
+    #[test]
+    fn pax_records() {
+        assert_eq!(
+            pax_path(b"20 mtime=1704067200\n12 path=a/b\n"),
+            Some("a/b".to_string())
+        );
+        assert_eq!(pax_path(b"20 mtime=1704067200\n"), None);
+        assert_eq!(pax_path(b"99 path=a\n"), None);
+        assert_eq!(pax_path(b"x path=a\n"), None);
+    }
+
+    #[tokio::test]
+    async fn deflated_files_stop_at_their_size() {
+        let text = b"0123456789".repeat(100);
+        let data = zip(&[("a.txt", 8, &deflate(&text), 10)]);
+        let path = std::env::temp_dir().join(format!("archive-fs-{}.zip", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let fs = ArchiveFileSystem::open(&path).unwrap();
+        let file = path.join("a.txt");
+        let contents = fs.get_file(&file).await.unwrap().read_to_end().await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(contents, b"0123456789");
+    }
+
+    #[test]
+    fn gzip_is_bounded() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&[0; 1000]).unwrap();
+        let data = encoder.finish().unwrap();
+        assert_eq!(gunzip(&data, 1000).unwrap().len(), 1000);
+        assert!(gunzip(&data, 999).is_err());
+    }
+
+    #[tokio::test]
+    async fn polling_stops_with_the_server() {
+        let mut tar = vec![];
+        tar_entry(&mut tar, "a.txt", b'0', b"a");
+        tar.extend_from_slice(&[0; 1024]);
+        let path = std::env::temp_dir().join(format!("archive-fs-{}.tar", std::process::id()));
+        std::fs::write(&path, tar).unwrap();
+        let fs = ArchiveFileSystem::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let signal = Arc::new(Signal::default());
+        assert!(fs.report_changes(signal.clone()));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(Arc::strong_count(&fs.inner), 2);
+        drop(signal);
+        tokio::time::sleep(POLL_INTERVAL * 2).await;
+        assert_eq!(Arc::strong_count(&fs.inner), 1);
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{css, http::percent_decode};
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A browser connected to the live-reload endpoint.
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub id: u64,
+    pub addr: SocketAddr,
+    pub user_agent: String,
+    /// Path of the page the client shows.
+    pub page: String,
+}
+
+impl Client {
+    pub fn new(addr: SocketAddr, user_agent: String, page: String) -> Self {
+        Self {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+            addr,
+            user_agent,
+            page,
+        }
+    }
+}
+
+/// State shared by all connected clients.
+#[derive(Debug, Default)]
+pub struct Clients {
+    /// Clients connected over any transport.
+    connected: Mutex<HashMap<u64, Client>>,
+    /// Files each page loaded, keyed like [`resource_key`].
+    resources: Mutex<HashMap<String, HashSet<String>>>,
+    /// Stylesheets each served stylesheet `@import`s.
+    imports: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl Clients {
+    /// Adds `client` to the connected clients until the returned guard is dropped.
+    pub fn register(self: &Arc<Self>, client: Client) -> Registration {
+        let id = client.id;
+        self.connected.lock().unwrap().insert(id, client);
+        Registration {
+            clients: self.clone(),
+            id,
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<Client> {
+        self.connected.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Forgets what `page` loaded before, since it is loaded again and reports its
+    /// resources anew. Pages no client shows anymore are forgotten too.
+    pub fn loaded(&self, page: &str) {
+        let page = resource_key(page);
+        let mut resources = self.resources.lock().unwrap();
+        self.prune(&mut resources);
+        resources.insert(page.clone(), served_files(&page));
+    }
+
+    /// Drops the resources of pages without a connected client.
+    fn prune(&self, resources: &mut HashMap<String, HashSet<String>>) {
+        let shown: HashSet<_> = self
+            .connected
+            .lock()
+            .unwrap()
+            .values()
+            .map(|v| resource_key(&v.page))
+            .collect();
+        resources.retain(|page, _| shown.contains(page));
+    }
+
+    /// Remembers that `page` loaded `resource`. Both are URL paths.
+    pub fn record(&self, page: &str, resource: &str) {
+        let page = resource_key(page);
+        let resource = resource_key(resource);
+        let mut resources = self.resources.lock().unwrap();
+        resources
+            .entry(page.clone())
+            .or_insert_with(|| served_files(&page))
+            .insert(resource);
+    }
+
+    /// Whether a client showing `page` has to reload because `changed` changed.
+    ///
+    /// Pages that never reported their resources, and paths outside the served
+    /// directory, always reload.
+    pub fn depends_on(&self, page: &str, changed: &Path) -> bool {
+        if changed.is_absolute() {
+            return true;
+        }
+        let changed = changed.to_string_lossy().replace('\\', "/");
+        match self.resources.lock().unwrap().get(&resource_key(page)) {
+            Some(resources) => resources.contains(&changed),
+            None => true,
+        }
+    }
+
+    /// Replaces the `@import`s recorded for the stylesheet at the URL path `stylesheet`.
+    pub fn record_imports(&self, stylesheet: &str, css: &str) {
+        let imports = css::imports(css)
+            .iter()
+            .filter_map(|url| css::resolve(stylesheet, url))
+            .map(|url| resource_key(&url))
+            .collect();
+        self.imports
+            .lock()
+            .unwrap()
+            .insert(resource_key(stylesheet), imports);
+    }
+
+    /// Stylesheets that directly or transitively `@import` `changed`.
+    pub fn importers(&self, changed: &Path) -> Vec<String> {
+        let imports = self.imports.lock().unwrap();
+        let mut found = vec![];
+        let mut queue = vec![changed.to_string_lossy().replace('\\', "/")];
+        while let Some(current) = queue.pop() {
+            for (importer, imported) in imports.iter() {
+                if imported.contains(&current) && !found.contains(importer) {
+                    found.push(importer.clone());
+                    queue.push(importer.clone());
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Keeps a client registered, see [`Clients::register`].
+pub struct Registration {
+    clients: Arc<Clients>,
+    id: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.clients.connected.lock().unwrap().remove(&self.id);
+        let mut resources = self.clients.resources.lock().unwrap();
+        self.clients.prune(&mut resources);
+    }
+}
+
+/// Whether the client showing `page` shows the file `changed` itself.
+pub fn is_page(page: &str, changed: &Path) -> bool {
+    let key = resource_key(page);
+    let changed = changed.to_string_lossy().replace('\\', "/");
+    // `/about` may be served from `about.html`
+    key == changed || changed.strip_suffix(".html") == Some(&key)
+}
+
+/// Whether the client showing `page` shows the directory `dir`.
+pub fn is_listing(page: &str, dir: &Path) -> bool {
+    let page = page.split(['?', '#']).next().unwrap_or_default();
+    percent_decode(page.trim_matches('/'), false) == dir.to_string_lossy().replace('\\', "/")
+}
+
+/// The files the page with the key `page` may be served from, since clean URLs serve
+/// `about.html` or `about/index.html` for `/about`.
+fn served_files(page: &str) -> HashSet<String> {
+    let mut files = HashSet::from([page.to_string()]);
+    if !page.ends_with(".html") {
+        files.insert(format!("{page}.html"));
+        files.insert(format!("{page}/index.html"));
+    }
+    files
+}
+
+/// Decoded path relative to the served directory, with `index.html` for directories.
+fn resource_key(path: &str) -> String {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let mut key = percent_decode(path.trim_start_matches('/'), false);
+    if key.is_empty() || key.ends_with('/') {
+        key.push_str("index.html");
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_urls_depend_on_their_html_file() {
+        let clients = Clients::default();
+        clients.record("/about", "/style.css");
+        assert!(clients.depends_on("/about", Path::new("about.html")));
+        assert!(clients.depends_on("/about", Path::new("style.css")));
+        assert!(!clients.depends_on("/about", Path::new("other.html")));
+        clients.record("/docs/", "/style.css");
+        assert!(clients.depends_on("/docs/", Path::new("docs/index.html")));
+        assert!(!clients.depends_on("/docs/", Path::new("docs.html")));
+    }
+
+    #[test]
+    fn full_loads_rebuild_the_resources() {
+        let clients = Arc::new(Clients::default());
+        let addr = "127.0.0.1:1".parse().unwrap();
+        let client = Client::new(addr, String::new(), "/".to_string());
+        let registration = clients.register(client);
+        clients.record("/", "/old.css");
+        clients.loaded("/");
+        clients.record("/", "/new.css");
+        assert!(!clients.depends_on("/", Path::new("old.css")));
+        assert!(clients.depends_on("/", Path::new("new.css")));
+        assert!(clients.depends_on("/", Path::new("index.html")));
+
+        // pages without clients are forgotten
+        clients.record("/forged", "/a.css");
+        clients.loaded("/");
+        assert!(clients.depends_on("/forged", Path::new("other.css")));
+        drop(registration);
+        assert!(clients.resources.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn pages_without_resources_always_reload() {
+        let clients = Clients::default();
+        assert!(clients.depends_on("/", Path::new("anything.txt")));
+    }
+}
@@ -0,0 +1,115 @@
+/// URLs of the `@import` rules in a stylesheet, in order.
+pub fn imports(css: &str) -> Vec<String> {
+    let mut imports = vec![];
+    let mut rest = css;
+    while let Some(start) = rest.find(['@', '/']) {
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map(|v| v.1).unwrap_or_default();
+            continue;
+        }
+        let Some(import) = strip_prefix_ignore_case(rest, "@import") else {
+            rest = &rest[1..];
+            continue;
+        };
+        let import = import.trim_start();
+        let (url, remaining) = match strip_prefix_ignore_case(import, "url(") {
+            Some(url) => {
+                let end = url.find(')').unwrap_or(url.len());
+                (url[..end].trim().trim_matches(['"', '\'']), &url[end..])
+            }
+            None => match import.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let url = &import[1..];
+                    let end = url.find(quote).unwrap_or(url.len());
+                    (&url[..end], &url[end..])
+                }
+                _ => ("", import),
+            },
+        };
+        if !url.is_empty() {
+            imports.push(url.to_string());
+        }
+        rest = remaining;
+    }
+    imports
+}
+
+/// Resolves `url` relative to the stylesheet at the URL path `base`. External URLs
+/// return `None`.
+pub fn resolve(base: &str, url: &str) -> Option<String> {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    if url.starts_with("//") || url.contains("://") || url.starts_with("data:") {
+        return None;
+    }
+    let mut segments: Vec<&str> = match url.starts_with('/') {
+        true => vec![],
+        false => base.split('/').collect(),
+    };
+    // drop the file name of the base
+    segments.pop();
+    for segment in url.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Some(format!("/{}", segments.join("/").trim_start_matches('/')))
+}
+
+fn strip_prefix_ignore_case<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = value.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &value[prefix.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_forms() {
+        let css = r#"
+            @import "a.css";
+            @import 'b.css' screen;
+            @IMPORT url(c.css);
+            @import url( "d.css" ) layer(base);
+            /* @import "commented.css"; */
+            @media print { body { color: red } }
+            @import url();
+        "#;
+        assert_eq!(imports(css), ["a.css", "b.css", "c.css", "d.css"]);
+    }
+
+    #[test]
+    fn unterminated_input() {
+        assert_eq!(imports("@import \"a.css"), ["a.css"]);
+        assert!(imports("/* @import \"a.css\";").is_empty());
+        assert!(imports("@import").is_empty());
+    }
+
+    #[test]
+    fn resolved_urls() {
+        assert_eq!(
+            resolve("/css/site.css", "base.css").as_deref(),
+            Some("/css/base.css")
+        );
+        assert_eq!(
+            resolve("/css/site.css", "../b.css?v=1").as_deref(),
+            Some("/b.css")
+        );
+        assert_eq!(
+            resolve("/css/site.css", "/x/./y.css").as_deref(),
+            Some("/x/y.css")
+        );
+        assert_eq!(
+            resolve("/site.css", "../../up.css").as_deref(),
+            Some("/up.css")
+        );
+        assert_eq!(resolve("/site.css", "https://cdn.test/a.css"), None);
+        assert_eq!(resolve("/site.css", "//cdn.test/a.css"), None);
+    }
+}
@@ -1,18 +1,85 @@
 use std::{
     future::Future,
     path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
 };
 
 use tokio::{
     fs::{read_dir, File as TokioFile, ReadDir},
-    io::AsyncReadExt as _,
+    io::{self, AsyncReadExt as _},
 };
 
-use crate::Error;
+use crate::{Error, Signal};
 
-pub trait FileSystemInterface: Clone + Send {
+/// Whether a path is a file or a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Dir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub kind: Kind,
+    /// Size in bytes, 0 for directories.
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+pub trait FileSystemInterface: Clone + Send + Sync {
     fn get_file(&self, path: &Path) -> impl Future<Output = crate::Result<impl File>> + Send;
     fn get_dir(&self, path: &Path) -> impl Future<Output = crate::Result<impl Dir>> + Send;
+    /// Fails if nothing exists at `path`.
+    fn metadata(&self, path: &Path) -> impl Future<Output = crate::Result<Metadata>> + Send;
+
+    /// Whether a file or directory exists at `path`.
+    fn exists(&self, path: &Path) -> impl Future<Output = bool> + Send {
+        async { self.metadata(path).await.is_ok() }
+    }
+
+    /// Whether `path` is a file or a directory, `None` if nothing exists there.
+    fn kind(&self, path: &Path) -> impl Future<Output = Option<Kind>> + Send {
+        async { self.metadata(path).await.ok().map(|v| v.kind) }
+    }
+
+    /// Creates or replaces the file at `path`. Used when [`crate::Options::writable`]
+    /// is set; read-only file systems keep the default, which fails.
+    fn write_file(
+        &self,
+        _path: &Path,
+        _contents: Vec<u8>,
+    ) -> impl Future<Output = crate::Result<()>> + Send {
+        async { Err(unsupported()) }
+    }
+
+    /// Creates the directory at `path`, whose parent has to exist.
+    fn create_dir(&self, _path: &Path) -> impl Future<Output = crate::Result<()>> + Send {
+        async { Err(unsupported()) }
+    }
+
+    /// Removes the file or the directory with all its contents at `path`.
+    fn remove(&self, _path: &Path) -> impl Future<Output = crate::Result<()>> + Send {
+        async { Err(unsupported()) }
+    }
+
+    /// Moves the file or directory `from` to `to`, which doesn't exist.
+    fn rename(&self, _from: &Path, _to: &Path) -> impl Future<Output = crate::Result<()>> + Send {
+        async { Err(unsupported()) }
+    }
+
+    /// Called once by [`crate::serve`] with its signal. File systems that notify it of
+    /// their own changes return `true`, so the served directory isn't watched on disk.
+    fn report_changes(&self, _signal: Arc<Signal>) -> bool {
+        false
+    }
+}
+
+fn unsupported() -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the file system is read-only",
+    ))
 }
 
 pub trait Dir: Send {
@@ -44,7 +111,7 @@ impl AsyncFile {
 impl File for AsyncFile {
     async fn read_to_end(&mut self) -> Vec<u8> {
         let mut buffer = vec![];
-        self.file.read_to_end(&mut buffer).await;
+        let _ = self.file.read_to_end(&mut buffer).await;
         buffer
     }
 }
@@ -70,4 +137,35 @@ impl FileSystemInterface for AsyncFileSystem {
     async fn get_file(&self, path: &Path) -> crate::Result<impl File> {
         AsyncFile::new(path).await
     }
+
+    async fn metadata(&self, path: &Path) -> crate::Result<Metadata> {
+        let metadata = tokio::fs::metadata(path).await?;
+        Ok(Metadata {
+            kind: match metadata.is_dir() {
+                true => Kind::Dir,
+                false => Kind::File,
+            },
+            len: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    async fn write_file(&self, path: &Path, contents: Vec<u8>) -> crate::Result<()> {
+        Ok(tokio::fs::write(path, contents).await?)
+    }
+
+    async fn create_dir(&self, path: &Path) -> crate::Result<()> {
+        Ok(tokio::fs::create_dir(path).await?)
+    }
+
+    async fn remove(&self, path: &Path) -> crate::Result<()> {
+        match tokio::fs::metadata(path).await?.is_dir() {
+            true => Ok(tokio::fs::remove_dir_all(path).await?),
+            false => Ok(tokio::fs::remove_file(path).await?),
+        }
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> crate::Result<()> {
+        Ok(tokio::fs::rename(from, to).await?)
+    }
 }
@@ -0,0 +1,81 @@
+/// Matches `path` against a glob `pattern`.
+///
+/// `*` matches within one path segment, `**` across segments, `?` a single character
+/// and `{a,b}` any of the listed alternatives. Backslashes in `path` are treated as `/`.
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let path = path.replace('\\', "/");
+    expand(pattern)
+        .iter()
+        .any(|pattern| match_bytes(pattern.as_bytes(), path.as_bytes()))
+}
+
+fn expand(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(close) = pattern[open..].find('}').map(|v| v + open) else {
+        return vec![pattern.to_string()];
+    };
+    let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+    pattern[open + 1..close]
+        .split(',')
+        .flat_map(|alt| expand(&format!("{prefix}{alt}{suffix}")))
+        .collect()
+}
+
+fn match_bytes(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            let rest = &pattern[2..];
+            match rest.strip_prefix(b"/") {
+                // `**/` matches zero or more whole segments
+                Some(rest) => {
+                    match_bytes(rest, path)
+                        || (0..path.len())
+                            .any(|i| path[i] == b'/' && match_bytes(rest, &path[i + 1..]))
+                }
+                None => (0..=path.len()).any(|i| match_bytes(rest, &path[i..])),
+            }
+        }
+        Some(b'*') => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != b'/')
+            .any(|i| match_bytes(&pattern[1..], &path[i..])),
+        Some(b'?') => !path.is_empty() && path[0] != b'/' && match_bytes(&pattern[1..], &path[1..]),
+        Some(&c) => path.first() == Some(&c) && match_bytes(&pattern[1..], &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn stars_and_question_marks() {
+        assert!(matches("*.css", "style.css"));
+        assert!(!matches("*.css", "css/style.css"));
+        assert!(matches("css/?.css", "css/a.css"));
+        assert!(!matches("css/?.css", "css/ab.css"));
+        assert!(!matches("a?b", "a/b"));
+    }
+
+    #[test]
+    fn double_stars_cross_segments() {
+        assert!(matches("**/*.css", "style.css"));
+        assert!(matches("**/*.css", "a/b/style.css"));
+        assert!(matches("src/**", "src/a/b.rs"));
+        assert!(matches("src/**/test.rs", "src/test.rs"));
+        assert!(!matches("src/**/test.rs", "lib/test.rs"));
+        assert!(matches("**", "any/path"));
+    }
+
+    #[test]
+    fn alternatives_and_backslashes() {
+        assert!(matches("*.{js,ts}", "app.ts"));
+        assert!(!matches("*.{js,ts}", "app.css"));
+        assert!(matches("{a,b}/{c,d}.txt", "b/c.txt"));
+        assert!(matches("src/**/*.rs", "src\\lib\\mod.rs"));
+        // an unclosed brace is matched literally
+        assert!(matches("{a", "{a"));
+    }
+}
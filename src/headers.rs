@@ -0,0 +1,162 @@
+use crate::{
+    glob,
+    http::{percent_decode, Request, Response},
+    Options,
+};
+
+/// Methods the server answers outside of its own endpoints.
+pub fn allow(options: &Options) -> &'static str {
+    match options.writable {
+        true => "GET, HEAD, OPTIONS, PUT, DELETE, MKCOL, POST",
+        false => "GET, HEAD, OPTIONS",
+    }
+}
+
+/// Adds the CORS headers, the matching [`crate::HeaderRule`]s and the cross-origin
+/// isolation headers to `response`.
+pub fn apply(options: &Options, request: &Request, response: &mut Response) {
+    if let Some(cors) = &options.cors {
+        if let Some(origin) = request.header("Origin") {
+            let any = cors.origins.iter().any(|v| v == "*");
+            if any && !cors.credentials {
+                response.set_header("Access-Control-Allow-Origin", "*");
+            } else if any || cors.origins.iter().any(|v| v == origin) {
+                response.set_header("Access-Control-Allow-Origin", origin);
+                let vary = match response.header("Vary") {
+                    Some(vary) => format!("{vary}, Origin"),
+                    None => "Origin".to_string(),
+                };
+                response.set_header("Vary", vary);
+                if cors.credentials {
+                    response.set_header("Access-Control-Allow-Credentials", "true");
+                }
+            }
+        }
+    }
+    let path = percent_decode(&request.path, false);
+    let path = path.trim_start_matches('/');
+    for rule in &options.headers {
+        if glob::matches(&rule.pattern, path) {
+            response.set_header(&rule.name, &rule.value);
+        }
+    }
+    if options.cross_origin_isolated {
+        response.set_header("Cross-Origin-Opener-Policy", "same-origin");
+        response.set_header("Cross-Origin-Embedder-Policy", "require-corp");
+        response.set_header("Cross-Origin-Resource-Policy", "same-origin");
+        // `WebAssembly.instantiateStreaming` rejects any other type
+        if path.ends_with(".wasm") && response.status == "200 OK" {
+            response.set_header("Content-Type", "application/wasm");
+        }
+    }
+}
+
+/// Whether a request comes from the server's own pages, an origin allowed with
+/// `--cors-origin` or a client that isn't a browser. Cross-site forms can post without
+/// a preflight, so requests that change files check this first.
+pub fn is_same_origin(options: &Options, request: &Request) -> bool {
+    let origin = request.header("Origin");
+    let allowed = options
+        .cors
+        .as_ref()
+        .is_some_and(|cors| origin.is_some_and(|origin| cors.origins.iter().any(|v| v == origin)));
+    if allowed {
+        return true;
+    }
+    if let Some(site) = request.header("Sec-Fetch-Site") {
+        if !matches!(site, "same-origin" | "none") {
+            return false;
+        }
+    }
+    match (origin, request.header("Host")) {
+        (Some(origin), Some(host)) => origin
+            .split_once("://")
+            .is_some_and(|(_, v)| v.eq_ignore_ascii_case(host)),
+        (Some(_), None) => false,
+        (None, _) => true,
+    }
+}
+
+/// Answers an `OPTIONS` request, including CORS preflights.
+pub fn preflight(options: &Options, request: &Request) -> Response {
+    let response = Response::new("204 No Content").with_header("Allow", allow(options));
+    let (Some(cors), Some(_)) = (
+        &options.cors,
+        request.header("Access-Control-Request-Method"),
+    ) else {
+        return response;
+    };
+    let headers = match cors.headers.is_empty() {
+        true => request
+            .header("Access-Control-Request-Headers")
+            .unwrap_or_default()
+            .to_string(),
+        false => cors.headers.join(", "),
+    };
+    let mut response =
+        response.with_header("Access-Control-Allow-Methods", cors.methods.join(", "));
+    if !headers.is_empty() {
+        response.set_header("Access-Control-Allow-Headers", headers);
+    }
+    if let Some(max_age) = cors.max_age {
+        response.set_header("Access-Control-Max-Age", max_age);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cors;
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        Request {
+            method: "POST".to_string(),
+            path: "/".to_string(),
+            query: None,
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            body: vec![],
+            too_large: false,
+        }
+    }
+
+    #[test]
+    fn cross_site_requests_are_rejected() {
+        let options = Options::default();
+        let host = ("Host", "localhost:8080");
+        assert!(is_same_origin(&options, &request(&[host])));
+        assert!(is_same_origin(
+            &options,
+            &request(&[host, ("Origin", "http://localhost:8080")])
+        ));
+        assert!(!is_same_origin(
+            &options,
+            &request(&[host, ("Origin", "https://evil.example")])
+        ));
+        assert!(!is_same_origin(
+            &options,
+            &request(&[host, ("Origin", "null")])
+        ));
+        assert!(!is_same_origin(
+            &options,
+            &request(&[host, ("Sec-Fetch-Site", "cross-site")])
+        ));
+    }
+
+    #[test]
+    fn cors_origins_are_allowed() {
+        let options = Options {
+            cors: Some(Cors {
+                origins: vec!["http://app.test".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let origin = ("Origin", "http://app.test");
+        let site = ("Sec-Fetch-Site", "cross-site");
+        assert!(is_same_origin(&options, &request(&[origin, site])));
+    }
+}
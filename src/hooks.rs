@@ -0,0 +1,189 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{
+    process::Command,
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+};
+
+use crate::{glob, BuildError, Hook, Signal};
+
+/// A file changed on disk. `served` is the path relative to the served directory.
+pub struct Change {
+    pub path: PathBuf,
+    pub served: Option<PathBuf>,
+}
+
+/// Spawns the task that runs hooks and returns the sender the watcher feeds.
+pub fn spawn(hooks: Vec<Hook>, signal: Arc<Signal>) -> UnboundedSender<Change> {
+    let (tx, rx) = unbounded_channel();
+    tokio::spawn(run(hooks, signal, rx));
+    tx
+}
+
+async fn run(hooks: Vec<Hook>, signal: Arc<Signal>, mut rx: UnboundedReceiver<Change>) {
+    let cwd = std::env::current_dir()
+        .and_then(|v| v.canonicalize())
+        .unwrap_or_default();
+    let matching = |changes: &[Change]| -> Vec<&Hook> {
+        hooks
+            .iter()
+            .filter(|hook| {
+                changes
+                    .iter()
+                    .any(|change| glob::matches(&hook.pattern, &display(&change.path, &cwd)))
+            })
+            .collect()
+    };
+    while let Some(change) = rx.recv().await {
+        let mut triggers = vec![change];
+        // changes that don't trigger a hook, e.g. build outputs, are held back until
+        // the hooks finished
+        let mut held = vec![];
+        'restart: loop {
+            while let Ok(change) = rx.try_recv() {
+                triggers.push(change);
+            }
+            let (trigger, other): (Vec<_>, Vec<_>) = triggers
+                .drain(..)
+                .partition(|v| !matching(std::slice::from_ref(v)).is_empty());
+            triggers = trigger;
+            held.extend(other);
+            let commands = matching(&triggers);
+            let ran = !commands.is_empty();
+            for hook in commands {
+                let child = match shell(&hook.command).spawn() {
+                    Ok(v) => v,
+                    Err(e) => {
+                        signal.send_error(failure(hook, &e.to_string()));
+                        held.clear();
+                        break 'restart;
+                    }
+                };
+                let group = Group(child.id());
+                let mut wait = Box::pin(child.wait_with_output());
+                let output = loop {
+                    tokio::select! {
+                        output = &mut wait => break output,
+                        change = rx.recv() => match change {
+                            Some(change) if !matching(std::slice::from_ref(&change)).is_empty() => {
+                                // dropping `wait` and `group` kills the stale run
+                                triggers.push(change);
+                                continue 'restart;
+                            }
+                            Some(change) => held.push(change),
+                            None => return,
+                        },
+                    }
+                };
+                group.finished();
+                match output {
+                    Ok(output) if output.status.success() => {
+                        #[cfg(feature = "log")]
+                        log::info!("`{}`\n{}", hook.command, combined(&output));
+                    }
+                    Ok(output) => {
+                        let message =
+                            format!("exited with {}\n\n{}", output.status, combined(&output));
+                        signal.send_error(failure(hook, &message));
+                        held.clear();
+                        break 'restart;
+                    }
+                    Err(e) => {
+                        signal.send_error(failure(hook, &e.to_string()));
+                        held.clear();
+                        break 'restart;
+                    }
+                }
+            }
+            // give the watcher a moment to report the outputs
+            if ran {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            while let Ok(change) = rx.try_recv() {
+                held.push(change);
+            }
+            let mut served: Vec<_> = held.drain(..).filter_map(|v| v.served).collect();
+            if served.is_empty() {
+                served = triggers
+                    .drain(..)
+                    .map(|v| v.served.unwrap_or(v.path))
+                    .collect();
+            }
+            served.sort();
+            served.dedup();
+            for path in served {
+                signal.send_signal(path);
+            }
+            break;
+        }
+    }
+}
+
+/// The process group of a running hook, killed when dropped before the hook finished,
+/// so commands the shell started don't outlive a cancelled run.
+#[cfg_attr(not(unix), allow(dead_code))]
+struct Group(Option<u32>);
+
+impl Group {
+    fn finished(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for Group {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0 {
+            // SAFETY: `kill` has no memory effects, the group is the one of the hook
+            unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+        }
+    }
+}
+
+fn display(path: &Path, cwd: &Path) -> String {
+    path.strip_prefix(cwd)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    };
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    // its own process group, so a cancelled run can be killed with its children
+    #[cfg(unix)]
+    cmd.process_group(0);
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    cmd
+}
+
+fn combined(output: &Output) -> String {
+    let mut out = String::from_utf8_lossy(&output.stdout).into_owned();
+    out.push_str(&String::from_utf8_lossy(&output.stderr));
+    out
+}
+
+fn failure(hook: &Hook, message: &str) -> BuildError {
+    BuildError {
+        message: format!("`{}` {}", hook.command, message),
+        ..Default::default()
+    }
+}
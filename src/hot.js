@@ -0,0 +1,28 @@
+// `import.meta.hot` for ES modules, imported by the prelude the server adds to modules
+// that use it. The registry is shared with updater.js.
+const modules = (globalThis.__rustyLiveServerModules ??= new Map());
+
+export default function createHot(url) {
+  const path = new URL(url).pathname;
+  const previous = modules.get(path);
+  const record = {
+    accepted: false,
+    acceptCallbacks: [],
+    disposeCallbacks: [],
+    data: previous?.nextData ?? {},
+  };
+  modules.set(path, record);
+  return {
+    data: record.data,
+    accept(callback) {
+      record.accepted = true;
+      if (callback) record.acceptCallbacks.push(callback);
+    },
+    dispose(callback) {
+      record.disposeCallbacks.push(callback);
+    },
+    invalidate() {
+      record.accepted = false;
+    },
+  };
+}
@@ -0,0 +1,381 @@
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt as _, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _,
+};
+
+use crate::inject::find;
+
+/// Largest body read unless uploads allow more.
+pub const MAX_BODY: usize = 16 * 1024 * 1024;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// The body was larger than allowed and wasn't read completely.
+    pub too_large: bool,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether the body was larger than allowed and wasn't read.
+    pub fn body_too_large(&self) -> bool {
+        self.too_large
+    }
+
+    /// Decoded `key=value` pairs of the query string.
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        self.query.as_deref().map(parse_form).unwrap_or_default()
+    }
+
+    /// Decoded `key=value` pairs of the query string followed by an urlencoded body.
+    pub fn form(&self) -> Vec<(String, String)> {
+        let mut pairs = self.query_pairs();
+        let urlencoded = self
+            .header("Content-Type")
+            .map(|v| v.starts_with("application/x-www-form-urlencoded"))
+            .unwrap_or(true);
+        if urlencoded {
+            pairs.extend(parse_form(&String::from_utf8_lossy(&self.body)));
+        }
+        pairs
+    }
+}
+
+/// Reads a request, with its body if that is at most `max_body` bytes. Bodies come
+/// with a `Content-Length` or in `Transfer-Encoding: chunked`.
+pub async fn read_request(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    max_body: usize,
+) -> Option<Request> {
+    let mut head = String::new();
+    loop {
+        let bytes_read = reader.read_line(&mut head).await.ok()?;
+        if bytes_read == 0 || head.ends_with("\r\n\r\n") || head == "\r\n" {
+            break;
+        }
+    }
+    let mut lines = head.lines();
+    let mut parts = lines.next()?.split_whitespace();
+    let (method, target, _version) = (parts.next()?, parts.next()?, parts.next()?);
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_string())),
+        None => (target, None),
+    };
+    let headers: Vec<_> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        headers,
+        body: vec![],
+        too_large: false,
+    };
+    let chunked = request
+        .header("Transfer-Encoding")
+        .is_some_and(|v| v.to_ascii_lowercase().contains("chunked"));
+    if chunked {
+        match read_chunks(reader, max_body).await? {
+            Some(body) => request.body = body,
+            None => request.too_large = true,
+        }
+        return Some(request);
+    }
+    let length = request
+        .header("Content-Length")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or_default();
+    if length > max_body {
+        request.too_large = true;
+    } else if length > 0 {
+        request.body = vec![0; length];
+        reader.read_exact(&mut request.body).await.ok()?;
+    }
+    Some(request)
+}
+
+/// Decodes a chunked body, or `Some(None)` once it exceeds `max_body` bytes.
+async fn read_chunks(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    max_body: usize,
+) -> Option<Option<Vec<u8>>> {
+    let mut body = vec![];
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.ok()?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).ok()?;
+        if size == 0 {
+            break;
+        }
+        if size > max_body.saturating_sub(body.len()) {
+            return Some(None);
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..]).await.ok()?;
+        line.clear();
+        reader.read_line(&mut line).await.ok()?;
+    }
+    // trailer fields up to the empty line
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line).await.ok()? {
+            0 => break,
+            _ if line.trim().is_empty() => break,
+            _ => {}
+        }
+    }
+    Some(Some(body))
+}
+
+pub fn parse_form(input: &str) -> Vec<(String, String)> {
+    input
+        .split('&')
+        .filter(|v| !v.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key, true), percent_decode(value, true))
+        })
+        .collect()
+}
+
+/// A part of a `multipart/form-data` body.
+pub struct Part {
+    pub name: String,
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Splits a `multipart/form-data` body, given the request's `Content-Type`.
+pub fn parse_multipart(content_type: &str, body: &[u8]) -> Vec<Part> {
+    let Some(boundary) = content_type
+        .split(';')
+        .find_map(|v| v.trim().strip_prefix("boundary="))
+    else {
+        return vec![];
+    };
+    let delimiter = format!("--{}", boundary.trim_matches('"'));
+    let mut parts = vec![];
+    let mut rest = match find(body, delimiter.as_bytes()) {
+        Some(i) => &body[i + delimiter.len()..],
+        None => return parts,
+    };
+    let delimiter = format!("\r\n{delimiter}");
+    // every part starts after a line break and ends before the next delimiter
+    while let Some(part) = rest.strip_prefix(b"\r\n") {
+        let Some(end) = find(part, delimiter.as_bytes()) else {
+            break;
+        };
+        rest = &part[end + delimiter.len()..];
+        let part = &part[..end];
+        let Some(head_end) = find(part, b"\r\n\r\n") else {
+            continue;
+        };
+        let head = String::from_utf8_lossy(&part[..head_end]);
+        let Some(disposition) = head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case("Content-Disposition")
+                .then_some(value)
+        }) else {
+            continue;
+        };
+        let param = |name: &str| {
+            disposition.split(';').find_map(|v| {
+                let value = v.trim().strip_prefix(name)?.strip_prefix('=')?;
+                Some(value.trim_matches('"').to_string())
+            })
+        };
+        parts.push(Part {
+            name: param("name").unwrap_or_default(),
+            filename: param("filename"),
+            data: part[head_end + 4..].to_vec(),
+        });
+    }
+    parts
+}
+
+/// Decodes `%XX` escapes and, for form data, `+` as space.
+pub fn percent_decode(input: &str, form: bool) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        out.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            b'+' if form => out.push(b' '),
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Encodes everything but unreserved characters, for a single path segment.
+pub fn percent_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            byte => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+pub struct Response {
+    pub status: &'static str,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: &'static str) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn ok(content_type: impl ToString, body: Vec<u8>) -> Self {
+        Self::new("200 OK")
+            .with_header("Content-Type", content_type)
+            .with_body(body)
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl ToString) -> Self {
+        self.set_header(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Sets `name`, replacing an existing header of the same name.
+    pub fn set_header(&mut self, name: &str, value: impl ToString) {
+        self.headers
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Writes the response, without the body for `HEAD` requests.
+    pub async fn write(
+        &self,
+        stream: &mut (impl AsyncWrite + Unpin),
+        head: bool,
+    ) -> std::io::Result<()> {
+        let length = match self.header("Content-Length") {
+            Some(_) => String::new(),
+            None => format!("Content-Length: {}\r\n", self.body.len()),
+        };
+        stream.write_all(self.head(&length).as_bytes()).await?;
+        if !head {
+            stream.write_all(&self.body).await?;
+        }
+        stream.flush().await
+    }
+
+    /// Writes only the status line and headers, for a body that is streamed
+    /// afterwards and ends when the connection closes.
+    pub async fn write_head(&self, stream: &mut (impl AsyncWrite + Unpin)) -> std::io::Result<()> {
+        stream.write_all(self.head("").as_bytes()).await
+    }
+
+    fn head(&self, extra: &str) -> String {
+        let mut response = format!("HTTP/1.1 {}\r\n", self.status);
+        for (name, value) in &self.headers {
+            response.push_str(&format!("{name}: {value}\r\n"));
+        }
+        response.push_str(extra);
+        response.push_str("Connection: close\r\n\r\n");
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn chunked_bodies() {
+        let input = b"PUT /a.txt HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nTrailer: x\r\n\r\n";
+        let request = read_request(&mut &input[..], 100).await.unwrap();
+        assert_eq!(request.body, b"hello, world");
+        assert!(!request.body_too_large());
+
+        let request = read_request(&mut &input[..], 8).await.unwrap();
+        assert!(request.body_too_large());
+        assert!(request.body.is_empty());
+
+        let broken = b"PUT / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        assert!(read_request(&mut &broken[..], 100).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn content_length_bodies() {
+        let input = b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nbody";
+        let request = read_request(&mut &input[..], 100).await.unwrap();
+        assert_eq!(request.body, b"body");
+        let request = read_request(&mut &input[..], 3).await.unwrap();
+        assert!(request.body_too_large());
+    }
+
+    #[test]
+    fn multipart_files_and_fields() {
+        let body = b"preamble\r\n--XyZ\r\n\
+Content-Disposition: form-data; name=\"file\"; filename=\"a b.txt\"\r\n\
+Content-Type: text/plain\r\n\r\n\
+line\r\n--not the end\r\n--XyZ\r\n\
+content-disposition: form-data; name=mkdir\r\n\r\n\
+docs\r\n--XyZ--\r\n";
+        let parts = parse_multipart("multipart/form-data; boundary=\"XyZ\"", body);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "file");
+        assert_eq!(parts[0].filename.as_deref(), Some("a b.txt"));
+        assert_eq!(parts[0].data, b"line\r\n--not the end");
+        assert_eq!(parts[1].name, "mkdir");
+        assert_eq!(parts[1].filename, None);
+        assert_eq!(parts[1].data, b"docs");
+    }
+
+    #[test]
+    fn multipart_without_boundary_or_end() {
+        assert!(parse_multipart("multipart/form-data", b"--a\r\n\r\n").is_empty());
+        let unterminated = b"--a\r\nContent-Disposition: form-data; name=x\r\n\r\ndata";
+        assert!(parse_multipart("multipart/form-data; boundary=a", unterminated).is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use sha2::{Digest as _, Sha256};
+
+use crate::{Injection, Options};
+
+pub const UPDATER_JS: &str = include_str!("updater.js");
+pub const HOT_JS: &str = include_str!("hot.js");
+
+/// The `sha256-…` source of the updater script, for `integrity` attributes and CSP.
+pub fn updater_hash() -> String {
+    format!(
+        "sha256-{}",
+        BASE64_STANDARD.encode(Sha256::digest(UPDATER_JS.as_bytes()))
+    )
+}
+
+pub fn is_html(mime: &mime_guess::Mime) -> bool {
+    matches!(mime.essence_str(), "text/html" | "application/xhtml+xml")
+}
+
+pub fn is_javascript(mime: &mime_guess::Mime) -> bool {
+    matches!(
+        mime.essence_str(),
+        "text/javascript" | "application/javascript"
+    )
+}
+
+/// Defines `import.meta.hot` in modules that use it. The prelude stays on the first
+/// line, after a shebang, so line numbers don't change.
+pub fn inject_hot(module: &mut Vec<u8>, options: &Options) {
+    if find(module, b"import.meta.hot").is_none() || !is_module(module) {
+        return;
+    }
+    let prelude = format!(
+        "import __rustyLiveServerHot from \"{}\";import.meta.hot = __rustyLiveServerHot(import.meta.url);",
+        options.endpoint("hot.js")
+    );
+    let start = match module.starts_with(b"#!") {
+        true => match find(module, b"\n") {
+            Some(i) => i + 1,
+            None => return,
+        },
+        false => 0,
+    };
+    module.splice(start..start, prelude.into_bytes());
+}
+
+/// Whether a script has a line starting with an `import` or `export` declaration, so
+/// it only runs as a module and the prelude's `import` is valid. Dynamic `import()`
+/// works in classic scripts too and doesn't count.
+fn is_module(script: &[u8]) -> bool {
+    script.split(|&c| c == b'\n').any(|line| {
+        let line = line.trim_ascii_start();
+        [&b"import"[..], b"export"].iter().any(|keyword| {
+            line.strip_prefix(*keyword).is_some_and(|rest| {
+                matches!(
+                    rest.first(),
+                    Some(b' ' | b'\t' | b'{' | b'*' | b'"' | b'\'')
+                )
+            })
+        })
+    })
+}
+
+/// Inserts the updater script tag before `</body>` or `</head>`, or at the end if the
+/// closing tag is missing.
+pub fn inject(html: &mut Vec<u8>, options: &Options) {
+    let tag = match options.injection {
+        Injection::Body => "</body",
+        Injection::Head => "</head",
+        Injection::Disabled => return,
+    };
+    let lower = html.to_ascii_lowercase();
+    let found = match options.injection {
+        Injection::Body => rfind(&lower, tag.as_bytes()),
+        _ => find(&lower, tag.as_bytes()),
+    };
+    let position = found
+        .or_else(|| rfind(&lower, b"</html"))
+        .unwrap_or(html.len());
+    let nonce = options
+        .csp_nonce
+        .clone()
+        .or_else(|| page_nonce(html, &lower));
+    let mut script = format!("<script src=\"{}\"", options.endpoint("updater.js"));
+    if let Some(nonce) = nonce {
+        script.push_str(&format!(" nonce=\"{nonce}\""));
+    }
+    if options.csp_hash {
+        script.push_str(&format!(" integrity=\"{}\"", updater_hash()));
+    }
+    script.push_str(" defer></script>");
+    html.splice(position..position, script.into_bytes());
+}
+
+/// The nonce of the first `<script nonce="…">` on the page.
+fn page_nonce(html: &[u8], lower: &[u8]) -> Option<String> {
+    let script = find(lower, b"<script")?;
+    let end = find(&lower[script..], b">")? + script;
+    let attr = find(&lower[script..end], b"nonce=")? + script + 6;
+    let quote = *html.get(attr)?;
+    let value = match quote {
+        b'"' | b'\'' => {
+            let len = html[attr + 1..end].iter().position(|&c| c == quote)?;
+            &html[attr + 1..attr + 1 + len]
+        }
+        _ => {
+            let len = html[attr..end]
+                .iter()
+                .position(|c| c.is_ascii_whitespace())
+                .unwrap_or(end - attr);
+            &html[attr..attr + len]
+        }
+    };
+    String::from_utf8(value.to_vec()).ok()
+}
+
+pub fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|v| v == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|v| v == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hot(script: &str) -> String {
+        let mut bytes = script.as_bytes().to_vec();
+        inject_hot(&mut bytes, &Options::default());
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn hot_prelude_only_in_modules() {
+        let module = "import { a } from \"./a.js\";\nimport.meta.hot.accept();\n";
+        assert!(hot(module).starts_with("import __rustyLiveServerHot from \"/__live/hot.js\";"));
+        assert!(hot(module).ends_with(module));
+        let exports = "export const a = 1;\nif (import.meta.hot) {}\n";
+        assert_ne!(hot(exports), exports);
+
+        let classic = "// import.meta.hot is only for modules\nconst m = import(\"./m.js\");\n";
+        assert_eq!(hot(classic), classic);
+        let unused = "import { a } from \"./a.js\";\n";
+        assert_eq!(hot(unused), unused);
+    }
+
+    #[test]
+    fn hot_prelude_after_shebang() {
+        let script = "#!/usr/bin/env node\nexport {};\nimport.meta.hot.accept();\n";
+        let out = hot(script);
+        assert!(out.starts_with("#!/usr/bin/env node\nimport __rustyLiveServerHot"));
+        assert_eq!(out.lines().count(), script.lines().count());
+    }
+}
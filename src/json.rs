@@ -0,0 +1,163 @@
+use std::{collections::HashMap, fmt::Write as _, iter::Peekable, str::Chars};
+
+/// Quotes and escapes `value` as a JSON string literal.
+pub fn string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn optional<T: ToString>(value: Option<T>) -> String {
+    value
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string())
+}
+
+/// The fields of a flat JSON object, with strings unescaped and other values as
+/// written. `None` for anything else, including nested objects and arrays.
+pub fn object(text: &str) -> Option<HashMap<String, String>> {
+    let mut chars = text.trim().chars().peekable();
+    let mut fields = HashMap::new();
+    if chars.next()? != '{' {
+        return None;
+    }
+    skip_space(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+        return chars.next().is_none().then_some(fields);
+    }
+    loop {
+        skip_space(&mut chars);
+        if chars.next()? != '"' {
+            return None;
+        }
+        let key = parse_string(&mut chars)?;
+        skip_space(&mut chars);
+        if chars.next()? != ':' {
+            return None;
+        }
+        skip_space(&mut chars);
+        let value = match chars.peek()? {
+            '"' => {
+                chars.next();
+                parse_string(&mut chars)?
+            }
+            '{' | '[' => return None,
+            _ => {
+                let mut value = String::new();
+                while let Some(c) = chars.next_if(|c| !matches!(c, ',' | '}') && !c.is_whitespace())
+                {
+                    value.push(c);
+                }
+                value
+            }
+        };
+        // like `JSON.parse`, the last of duplicate keys wins
+        fields.insert(key, value);
+        skip_space(&mut chars);
+        match chars.next()? {
+            ',' => continue,
+            '}' => break,
+            _ => return None,
+        }
+    }
+    skip_space(&mut chars);
+    chars.next().is_none().then_some(fields)
+}
+
+fn skip_space(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// The rest of a string literal after its opening quote.
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut out = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(out),
+            '\\' => out.push(match chars.next()? {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'u' => {
+                    let mut code = parse_hex(chars)?;
+                    // a surrogate pair, escaped as two code units
+                    if (0xd800..0xdc00).contains(&code) && chars.next_if_eq(&'\\').is_some() {
+                        if chars.next()? != 'u' {
+                            return None;
+                        }
+                        let low = parse_hex(chars)?;
+                        code = match (0xdc00..0xe000).contains(&low) {
+                            true => 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00),
+                            false => 0xfffd,
+                        };
+                    }
+                    char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                }
+                c => c,
+            }),
+            c => out.push(c),
+        }
+    }
+}
+
+fn parse_hex(chars: &mut Peekable<Chars>) -> Option<u32> {
+    let hex: String = (0..4).filter_map(|_| chars.next()).collect();
+    u32::from_str_radix(&hex, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_flat_objects() {
+        let fields = object(r#"{"type":"scroll","x":0.5, "ok" : true}"#).unwrap();
+        assert_eq!(fields["type"], "scroll");
+        assert_eq!(fields["x"], "0.5");
+        assert_eq!(fields["ok"], "true");
+        assert!(object("{}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn unescapes_strings_like_json_parse() {
+        let fields = object(r#"{"url":"a\"b\\c\n😀","url":"last"}"#).unwrap();
+        assert_eq!(fields["url"], "last");
+        let fields = object(r#"{"v":"a\"b\\c\n😀"}"#).unwrap();
+        assert_eq!(fields["v"], "a\"b\\c\n😀");
+        let fields = object(r#"{"v":"\u0061\ud83d\ude00"}"#).unwrap();
+        assert_eq!(fields["v"], "a😀");
+    }
+
+    #[test]
+    fn rejects_other_json() {
+        assert!(object(r#"{"a":{"b":1}}"#).is_none());
+        assert!(object(r#"{"a":[1]}"#).is_none());
+        assert!(object(r#"{"a":"b""#).is_none());
+        assert!(object(r#"{"a":"b"} x"#).is_none());
+        assert!(object("[]").is_none());
+    }
+
+    #[test]
+    fn round_trips_string() {
+        let text = "quote \" backslash \\ tab \t bell \u{7}";
+        let fields = object(&format!("{{\"v\":{}}}", string(text))).unwrap();
+        assert_eq!(fields["v"], text);
+    }
+}
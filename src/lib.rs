@@ -1,7 +1,30 @@
+mod archive;
+mod archive_fs;
+mod clients;
+mod css;
 mod fs;
+mod glob;
+mod headers;
+#[cfg(feature = "filesystem-events")]
+mod hooks;
+mod http;
+mod inject;
+mod json;
+mod listing;
+mod live;
+mod memory;
+mod netlify;
+mod options;
+mod remote_console;
 mod routing;
 mod signal;
+mod sse;
+mod upload;
+#[cfg(feature = "webdav")]
+mod webdav;
 mod websocket;
+#[cfg(feature = "webdav")]
+mod xml;
 
 #[cfg(feature = "filesystem-events")]
 use std::collections::HashMap;
@@ -9,20 +32,29 @@ use std::collections::HashMap;
 use std::io::Read;
 #[cfg(feature = "filesystem-events")]
 use std::path::Path;
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
+use clients::Clients;
 use routing::handle_client;
-pub use signal::Signal;
+pub use signal::{BuildError, Signal};
 use tokio::{io, net::TcpListener};
 
+pub use archive_fs::ArchiveFileSystem;
 pub use fs::AsyncFileSystem;
 pub use fs::Dir;
 pub use fs::File;
 pub use fs::FileSystemInterface;
+pub use fs::{Kind, Metadata};
+pub use inject::updater_hash;
+pub use memory::MemoryFileSystem;
 #[cfg(feature = "filesystem-events")]
 use notify::event::{CreateKind, ModifyKind};
 #[cfg(feature = "filesystem-events")]
 use notify::{Event, EventKind, RecursiveMode, Watcher as _};
+pub use options::{Cors, HeaderRule, Hook, Injection, Options};
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -44,6 +76,19 @@ impl From<notify::Error> for Error {
     }
 }
 
+/// State shared by all connections.
+pub(crate) struct Context<T> {
+    pub base_dir: PathBuf,
+    pub signal: Arc<Signal>,
+    pub options: Options,
+    pub clients: Arc<Clients>,
+    /// Rules from `_headers` and `_redirects`, replaced when either file changes.
+    pub rules: Mutex<Arc<netlify::Rules>>,
+    #[cfg(feature = "webdav")]
+    pub dav: webdav::Dav,
+    pub fs: T,
+}
+
 #[cfg(feature = "filesystem-events")]
 fn b3sum(path: &Path) -> io::Result<blake3::Hash> {
     let mut file = std::fs::File::open(path)?;
@@ -58,18 +103,63 @@ pub async fn serve<T: FileSystemInterface + 'static>(
     global: bool,
     signal: Option<Signal>,
     fs: T,
+) -> Result<()> {
+    serve_with_options(path, port, global, signal, fs, Options::default()).await
+}
+
+/// Like [`serve`], with additional [`Options`].
+///
+/// [`Hook`] patterns are matched against the changed file relative to the current
+/// working directory, e.g. `html/**/*.scss` or `src/*.rs`.
+pub async fn serve_with_options<T: FileSystemInterface + 'static>(
+    path: PathBuf,
+    port: u16,
+    global: bool,
+    signal: Option<Signal>,
+    fs: T,
+    options: Options,
 ) -> Result<()> {
     let signal = Arc::new(signal.unwrap_or_default());
+    // file systems that report their own changes aren't watched on disk
+    #[cfg(feature = "filesystem-events")]
+    let watch_served = !fs.report_changes(signal.clone());
+    #[cfg(not(feature = "filesystem-events"))]
+    fs.report_changes(signal.clone());
     #[cfg(feature = "filesystem-events")]
     let s = signal.clone();
     #[cfg(feature = "filesystem-events")]
-    let abs_path = std::fs::canonicalize(&path)?;
+    let abs_path = match watch_served {
+        true => std::fs::canonicalize(&path)?,
+        false => path.clone(),
+    };
+    #[cfg(feature = "filesystem-events")]
+    let hooks = match options.hooks.is_empty() {
+        true => None,
+        false => Some(hooks::spawn(options.hooks.clone(), signal.clone())),
+    };
     #[cfg(feature = "filesystem-events")]
     let mut file_table = HashMap::new();
     #[cfg(feature = "filesystem-events")]
     let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
         Ok(mut event) => {
             let kind = event.kind;
+            if matches!(
+                kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_))
+            ) {
+                let mut dirs: Vec<_> = event
+                    .paths
+                    .iter()
+                    .filter_map(|v| v.parent()?.canonicalize().ok())
+                    .filter_map(|v| Some(v.strip_prefix(&abs_path).ok()?.to_path_buf()))
+                    .collect();
+                dirs.dedup();
+                for dir in dirs {
+                    s.send_dir_changed(dir);
+                }
+            }
             if matches!(
                 kind,
                 EventKind::Create(CreateKind::File)
@@ -79,24 +169,35 @@ pub async fn serve<T: FileSystemInterface + 'static>(
                 if let Some(changed_file) = event.paths.pop() {
                     if let Ok(hash) = b3sum(&changed_file) {
                         if let Ok(changed_file) = changed_file.canonicalize() {
-                            if let Ok(rel_path) = changed_file.strip_prefix(abs_path.clone()) {
-                                let changed = match file_table.entry(changed_file.clone()) {
-                                    std::collections::hash_map::Entry::Occupied(v) => {
-                                        let mu: &mut blake3::Hash = v.into_mut();
-                                        if *mu != hash {
-                                            *mu = hash;
-                                            true
-                                        } else {
-                                            false
-                                        }
-                                    }
-                                    std::collections::hash_map::Entry::Vacant(v) => {
-                                        v.insert(hash);
+                            let changed = match file_table.entry(changed_file.clone()) {
+                                std::collections::hash_map::Entry::Occupied(v) => {
+                                    let mu: &mut blake3::Hash = v.into_mut();
+                                    if *mu != hash {
+                                        *mu = hash;
                                         true
+                                    } else {
+                                        false
+                                    }
+                                }
+                                std::collections::hash_map::Entry::Vacant(v) => {
+                                    v.insert(hash);
+                                    true
+                                }
+                            };
+                            let rel_path = changed_file
+                                .strip_prefix(&abs_path)
+                                .ok()
+                                .map(|v| v.to_path_buf());
+                            if changed {
+                                match (&hooks, rel_path) {
+                                    (Some(hooks), served) => {
+                                        let _ = hooks.send(hooks::Change {
+                                            path: changed_file,
+                                            served,
+                                        });
                                     }
-                                };
-                                if changed {
-                                    s.send_signal(rel_path.to_path_buf());
+                                    (None, Some(rel_path)) => s.send_signal(rel_path),
+                                    (None, None) => {}
                                 }
                             }
                         }
@@ -111,20 +212,36 @@ pub async fn serve<T: FileSystemInterface + 'static>(
     })?;
 
     #[cfg(feature = "filesystem-events")]
-    watcher.watch(path.as_path(), RecursiveMode::Recursive)?;
+    if watch_served {
+        watcher.watch(path.as_path(), RecursiveMode::Recursive)?;
+    }
+    #[cfg(feature = "filesystem-events")]
+    for extra in &options.watch {
+        watcher.watch(extra, RecursiveMode::Recursive)?;
+    }
     let addr = match global {
         true => format!("0.0.0.0:{port}"),
         false => format!("127.0.0.1:{port}"),
     };
     let listener = TcpListener::bind(addr).await?;
+    let rules = netlify::Rules::load(&fs, &path).await;
+    let ctx = Arc::new(Context {
+        base_dir: path,
+        signal,
+        options,
+        clients: Arc::default(),
+        rules: Mutex::new(Arc::new(rules)),
+        #[cfg(feature = "webdav")]
+        dav: Default::default(),
+        fs,
+    });
+    tokio::spawn(netlify::watch(ctx.clone()));
     loop {
         match listener.accept().await {
-            Ok((stream, _)) => {
-                let path = path.clone();
-                let signal = signal.clone();
-                let fs = fs.clone();
+            Ok((stream, addr)) => {
+                let ctx = ctx.clone();
                 tokio::spawn(async move {
-                    handle_client(stream, path, signal, fs).await;
+                    handle_client(stream, addr, ctx).await;
                 });
             }
             Err(_e) => {
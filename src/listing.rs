@@ -0,0 +1,361 @@
+//! The page served for directories without an `index.html`.
+
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    http::{percent_decode, percent_encode},
+    json, Dir, FileSystemInterface, Kind, Options,
+};
+
+/// A file or folder in a listed directory.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl Entry {
+    pub fn is_hidden(&self) -> bool {
+        self.name.starts_with('.')
+    }
+}
+
+/// How entries are ordered and paged, from the `sort`, `order`, `hidden`, `offset`
+/// and `limit` query parameters.
+#[derive(Debug, Clone, Default)]
+pub struct View {
+    pub sort: Sort,
+    pub descending: bool,
+    pub hidden: bool,
+    /// Requested with `?format=json`.
+    pub json: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// Entries per page of a JSON listing without `limit`.
+const DEFAULT_LIMIT: usize = 1000;
+/// Most entries per page of a JSON listing.
+const MAX_LIMIT: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sort {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+impl Sort {
+    fn as_str(self) -> &'static str {
+        match self {
+            Sort::Name => "name",
+            Sort::Size => "size",
+            Sort::Modified => "modified",
+        }
+    }
+}
+
+impl View {
+    pub fn from_query(pairs: &[(String, String)]) -> Self {
+        let mut view = Self::default();
+        for (key, value) in pairs {
+            match (key.as_str(), value.as_str()) {
+                ("sort", "size") => view.sort = Sort::Size,
+                ("sort", "modified") => view.sort = Sort::Modified,
+                ("order", "desc") => view.descending = true,
+                ("hidden", "1" | "true") => view.hidden = true,
+                ("format", "json") => view.json = true,
+                ("offset", offset) => view.offset = offset.parse().unwrap_or_default(),
+                ("limit", limit) => {
+                    view.limit = limit
+                        .parse()
+                        .ok()
+                        .filter(|v| *v > 0)
+                        .map(|v: usize| v.min(MAX_LIMIT))
+                }
+                _ => {}
+            }
+        }
+        view
+    }
+
+    /// Drops hidden entries unless requested and sorts the rest, folders first.
+    pub fn apply(&self, entries: &mut Vec<Entry>) {
+        if !self.hidden {
+            entries.retain(|v| !v.is_hidden());
+        }
+        entries.sort_by(|a, b| {
+            let order = match self.sort {
+                Sort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                Sort::Size => a.size.cmp(&b.size),
+                Sort::Modified => a.modified.cmp(&b.modified),
+            };
+            let order = match self.descending {
+                true => order.reverse(),
+                false => order,
+            };
+            b.is_dir.cmp(&a.is_dir).then(order)
+        });
+    }
+
+    fn query(&self, sort: Sort, descending: bool, hidden: bool) -> String {
+        let mut query = format!("?sort={}", sort.as_str());
+        if descending {
+            query.push_str("&order=desc");
+        }
+        if hidden {
+            query.push_str("&hidden=1");
+        }
+        query
+    }
+}
+
+/// Reads all entries of `dir` with their metadata.
+pub async fn read(fs: &impl FileSystemInterface, dir: &Path) -> crate::Result<Vec<Entry>> {
+    let mut entries = vec![];
+    let mut dir_entries = fs.get_dir(dir).await?;
+    while let Ok(Some(path)) = dir_entries.get_next().await {
+        let Some(name) = path.file_name().map(|v| v.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let metadata = fs.metadata(&path).await.ok();
+        entries.push(Entry {
+            name,
+            is_dir: metadata.is_some_and(|v| v.kind == Kind::Dir),
+            size: metadata.map(|v| v.len).unwrap_or_default(),
+            modified: metadata.and_then(|v| v.modified),
+        });
+    }
+    Ok(entries)
+}
+
+/// One page of the listing as JSON, with the URL of the next page if there is one.
+pub fn render_json(url: &str, entries: &[Entry], view: &View) -> String {
+    let limit = view.limit.unwrap_or(DEFAULT_LIMIT);
+    let page = entries.iter().skip(view.offset).take(limit);
+    let items: Vec<_> = page
+        .map(|entry| {
+            let (kind, mime) = match entry.is_dir {
+                true => ("directory", None),
+                false => (
+                    "file",
+                    Some(json::string(
+                        mime_guess::from_path(&entry.name)
+                            .first_or_octet_stream()
+                            .essence_str(),
+                    )),
+                ),
+            };
+            let mtime = entry
+                .modified
+                .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+                .map(|v| v.as_secs());
+            format!(
+                "{{\"name\":{},\"type\":\"{kind}\",\"size\":{},\"mtime\":{},\"mime\":{}}}",
+                json::string(&entry.name),
+                entry.size,
+                json::optional(mtime),
+                json::optional(mime),
+            )
+        })
+        .collect();
+    let end = view.offset.saturating_add(limit);
+    let next = (end < entries.len()).then(|| {
+        let mut query = view.query(view.sort, view.descending, view.hidden);
+        query.push_str(&format!("&format=json&offset={end}&limit={limit}"));
+        json::string(&format!("{url}{query}"))
+    });
+    format!(
+        "{{\"path\":{},\"total\":{},\"offset\":{},\"limit\":{limit},\"next\":{},\"entries\":[{}]}}",
+        json::string(&percent_decode(url, false)),
+        entries.len(),
+        view.offset,
+        json::optional(next),
+        items.join(",")
+    )
+}
+
+/// The listing page of the directory at the URL path `url`, which ends with `/`, with
+/// download links and upload forms as far as `options` enable them.
+pub fn render(url: &str, entries: &[Entry], view: &View, options: &Options) -> String {
+    let title = escape(&percent_decode(url, false));
+    let mut html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width\"><title>Index of {title}</title><style>{STYLE}</style></head><body>"
+    );
+    html.push_str("<h1>");
+    html.push_str(&breadcrumbs(url));
+    html.push_str("</h1><table><thead><tr>");
+    for (sort, label) in [
+        (Sort::Name, "Name"),
+        (Sort::Size, "Size"),
+        (Sort::Modified, "Modified"),
+    ] {
+        let descending = view.sort == sort && !view.descending;
+        let arrow = match (view.sort == sort, view.descending) {
+            (true, false) => " ↑",
+            (true, true) => " ↓",
+            (false, _) => "",
+        };
+        html.push_str(&format!(
+            "<th><a href=\"{}\">{label}{arrow}</a></th>",
+            escape(&view.query(sort, descending, view.hidden))
+        ));
+    }
+    html.push_str("</tr></thead><tbody>");
+    if url != "/" {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>");
+    }
+    for entry in entries {
+        let slash = if entry.is_dir { "/" } else { "" };
+        let size = match entry.is_dir {
+            true => "—".to_string(),
+            false => format_size(entry.size),
+        };
+        html.push_str(&format!(
+            "<tr class=\"{}\"><td><a href=\"{}{slash}\">{}{slash}</a></td><td>{size}</td><td>{}</td></tr>",
+            if entry.is_dir { "dir" } else { "file" },
+            percent_encode(&entry.name),
+            escape(&entry.name),
+            entry.modified.map(format_time).unwrap_or_default(),
+        ));
+    }
+    html.push_str("</tbody></table>");
+    html.push_str(&format!(
+        "<p><a href=\"{}\">{} hidden files</a>",
+        escape(&view.query(view.sort, view.descending, !view.hidden)),
+        if view.hidden { "Hide" } else { "Show" },
+    ));
+    if options.archives {
+        html.push_str(
+            " · Download as <a href=\"?download=zip\">ZIP</a> or <a href=\"?download=tar.gz\">tar.gz</a>",
+        );
+    }
+    html.push_str("</p>");
+    if options.writable {
+        html.push_str(UPLOAD_FORMS);
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+const UPLOAD_FORMS: &str = "<form method=\"post\" enctype=\"multipart/form-data\"><input type=\"file\" name=\"file\" multiple required> <button>Upload</button></form><form method=\"post\"><input name=\"mkdir\" placeholder=\"Folder name\" required> <button>New folder</button></form>";
+
+const STYLE: &str = "body{font:14px system-ui,sans-serif;margin:2em}h1{font-size:1.2em}h1 a{text-decoration:none}table{border-collapse:collapse;min-width:50%}th,td{text-align:left;padding:.25em 1em .25em 0}td+td{color:#666;white-space:nowrap}th a{color:inherit}form{margin:.5em 0}tr.dir a{font-weight:600}";
+
+/// Links to every ancestor of the URL path `url`.
+fn breadcrumbs(url: &str) -> String {
+    let mut html = "<a href=\"/\">/</a>".to_string();
+    let mut href = "/".to_string();
+    for segment in url.split('/').filter(|v| !v.is_empty()) {
+        href.push_str(segment);
+        href.push('/');
+        html.push_str(&format!(
+            "<a href=\"{}\">{}</a>/",
+            escape(&href),
+            escape(&percent_decode(segment, false))
+        ));
+    }
+    html
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn format_size(size: u64) -> String {
+    let mut value = size as f64;
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if value < 1024.0 {
+            return match unit {
+                "B" => format!("{size} B"),
+                _ => format!("{value:.1} {unit}"),
+            };
+        }
+        value /= 1024.0;
+    }
+    format!("{value:.1} TiB")
+}
+
+/// `YYYY-MM-DD HH:MM` in UTC.
+fn format_time(time: SystemTime) -> String {
+    let (year, month, day, secs) = civil(time);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
+
+/// Year, month, day and seconds of the day of `time` in UTC.
+pub fn civil(time: SystemTime) -> (i64, i64, i64, u64) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or_default();
+    let (days, rest) = (secs / 86400, secs % 86400);
+    // civil date from days since 1970-01-01, by Howard Hinnant
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(count: usize) -> Vec<Entry> {
+        (0..count)
+            .map(|i| Entry {
+                name: format!("{i}.txt"),
+                is_dir: false,
+                size: 1,
+                modified: None,
+            })
+            .collect()
+    }
+
+    fn view(query: &str) -> View {
+        View::from_query(&crate::http::parse_form(query))
+    }
+
+    #[test]
+    fn limits_are_clamped() {
+        assert_eq!(view("limit=0").limit, None);
+        assert_eq!(view("limit=5").limit, Some(5));
+        assert_eq!(view("limit=18446744073709551615").limit, Some(MAX_LIMIT));
+    }
+
+    #[test]
+    fn pages_link_to_the_next() {
+        let json = render_json("/d/", &entries(3), &view("offset=1&limit=1"));
+        assert!(json.contains("\"next\":\"/d/?sort=name&format=json&offset=2&limit=1\""));
+        let json = render_json("/d/", &entries(3), &view("offset=2&limit=1"));
+        assert!(json.contains("\"next\":null"));
+        let json = render_json("/d/", &entries(3), &view("offset=18446744073709551615"));
+        assert!(json.contains("\"next\":null,\"entries\":[]"));
+    }
+}
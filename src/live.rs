@@ -0,0 +1,203 @@
+//! The live-reload protocol, shared by the WebSocket and Server-Sent Events transports.
+//!
+//! Messages to the browser are text, e.g. `reload`, `update-css:///style.css` or
+//! `config://{…}`. Messages from the browser use the same `kind://payload` form.
+
+use std::path::Path;
+
+use crate::{
+    clients::{is_listing, is_page, Client},
+    fs::File as _,
+    inject::{inject, is_html, is_javascript},
+    json, remote_console,
+    signal::Event,
+    Context, FileSystemInterface, Kind,
+};
+
+/// How a changed file is applied in the browser.
+enum Asset {
+    /// Stylesheets are swapped.
+    Css,
+    /// Pages reload, or are morphed if enabled.
+    Html,
+    /// ES modules are re-imported if they accept hot updates.
+    Script,
+    /// Images, fonts, audio and video are re-fetched in place.
+    Media,
+    /// Everything else reloads the page.
+    Other,
+}
+
+impl Asset {
+    fn classify(path: &Path) -> Self {
+        let mime = mime_guess::from_path(path).first_or_octet_stream();
+        match (mime.type_(), mime.subtype()) {
+            (mime_guess::mime::TEXT, mime_guess::mime::CSS) => Self::Css,
+            _ if is_html(&mime) => Self::Html,
+            _ if is_javascript(&mime) => Self::Script,
+            (mime_guess::mime::IMAGE, _)
+            | (mime_guess::mime::FONT, _)
+            | (mime_guess::mime::AUDIO, _)
+            | (mime_guess::mime::VIDEO, _) => Self::Media,
+            // e.g. `application/font-woff`
+            (mime_guess::mime::APPLICATION, subtype) if subtype.as_str().starts_with("font-") => {
+                Self::Media
+            }
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Messages sent when a client connects.
+pub fn handshake(ctx: &Context<impl FileSystemInterface>, client: &Client) -> Vec<String> {
+    let mut messages = vec![format!("config://{}", ctx.options.client_config(client.id))];
+    if let Some(error) = ctx.signal.current_error() {
+        messages.push(format!("error://{}", error.to_json()));
+    }
+    messages
+}
+
+/// The message for `event`, or `None` if it doesn't concern `client`.
+pub async fn message(
+    ctx: &Context<impl FileSystemInterface>,
+    client: &Client,
+    event: Event,
+) -> Option<String> {
+    let msg = match event {
+        Event::Changed(file_path) => match Asset::classify(&file_path) {
+            Asset::Css => {
+                // stylesheets that `@import` the file have to be swapped instead
+                let mut msg = format!("update-css:///{}", file_path.display());
+                for importer in ctx.clients.importers(&file_path) {
+                    msg.push_str(&format!("\n/{importer}"));
+                }
+                msg
+            }
+            Asset::Media => format!("update-asset:///{}", file_path.display()),
+            Asset::Html if ctx.options.morph && is_page(&client.page, &file_path) => {
+                match morph_message(ctx, &file_path).await {
+                    Some(msg) => msg,
+                    None => "reload".to_string(),
+                }
+            }
+            Asset::Script
+                if ctx.options.hot && ctx.clients.depends_on(&client.page, &file_path) =>
+            {
+                format!("hot:///{}", file_path.display())
+            }
+            Asset::Html | Asset::Script | Asset::Other
+                if ctx.clients.depends_on(&client.page, &file_path) =>
+            {
+                "reload".to_string()
+            }
+            // a successful change still removes the overlay of a previous build error
+            Asset::Html | Asset::Script | Asset::Other => "clear-error".to_string(),
+        },
+        // directory listings refresh when entries come and go
+        Event::DirChanged(dir) => {
+            let index = ctx.base_dir.join(&dir).join("index.html");
+            if !is_listing(&client.page, &dir) || ctx.fs.kind(&index).await == Some(Kind::File) {
+                return None;
+            }
+            "reload".to_string()
+        }
+        Event::Error(error) => format!("error://{}", error.to_json()),
+        Event::ClearError => "clear-error".to_string(),
+        Event::Sync { from, page, data } => {
+            if from == client.id || page != client.page {
+                return None;
+            }
+            format!("sync://{data}")
+        }
+    };
+    Some(msg)
+}
+
+/// Handles a message from the browser.
+pub async fn receive(ctx: &Context<impl FileSystemInterface>, client: &Client, text: &str) {
+    if let Some(entry) = text.strip_prefix("console://") {
+        remote_console::print(&ctx.options, client, entry).await;
+    } else if let Some(data) = text.strip_prefix("sync://") {
+        if ctx.options.sync && is_safe_sync(data) {
+            ctx.signal.send_event(Event::Sync {
+                from: client.id,
+                page: client.page.clone(),
+                data: data.to_string(),
+            });
+        }
+    } else if let Some(resources) = text.strip_prefix("resources://") {
+        for resource in resources.lines() {
+            ctx.clients.record(&client.page, resource);
+        }
+    }
+}
+
+/// Whether the interaction `data` may be mirrored to other clients. Navigation has to
+/// stay on the server, since any client can send it.
+fn is_safe_sync(data: &str) -> bool {
+    let Some(fields) = json::object(data) else {
+        return false;
+    };
+    match fields.get("type").map(String::as_str) {
+        Some("navigate") => fields.get("url").is_some_and(|v| is_local_path(v)),
+        _ => true,
+    }
+}
+
+/// A path on this server, which browsers can't resolve to another origin. They treat
+/// `\` like `/` and drop tabs and newlines, so neither is allowed.
+fn is_local_path(url: &str) -> bool {
+    url.starts_with('/')
+        && !url.starts_with("//")
+        && !url.chars().any(|c| c == '\\' || c.is_control())
+}
+
+/// The new document with the updater script, for morphing the page in place.
+async fn morph_message(
+    ctx: &Context<impl FileSystemInterface>,
+    file_path: &Path,
+) -> Option<String> {
+    let mut contents = ctx
+        .fs
+        .get_file(&ctx.base_dir.join(file_path))
+        .await
+        .ok()?
+        .read_to_end()
+        .await;
+    inject(&mut contents, &ctx.options);
+    Some(format!(
+        "morph:///{}\n{}",
+        file_path.display(),
+        String::from_utf8_lossy(&contents)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_navigation_stays_on_the_server() {
+        assert!(is_safe_sync(
+            r#"{"type":"navigate","url":"/about?x=1#top"}"#
+        ));
+        assert!(is_safe_sync(r#"{"type":"scroll","x":0,"y":1}"#));
+        for url in [
+            "javascript:alert(1)",
+            "https://example.com/",
+            "//example.com/",
+            "/\\example.com/",
+            "/\t/example.com/",
+            "about",
+        ] {
+            let data = format!("{{\"type\":\"navigate\",\"url\":{}}}", json::string(url));
+            assert!(!is_safe_sync(&data), "{url}");
+        }
+        // escapes are decoded like the browser does
+        assert!(!is_safe_sync(
+            r#"{"type":"navig\u0061te","url":"\u006aavascript:x"}"#
+        ));
+        assert!(!is_safe_sync(r#"{"type":"navigate"}"#));
+        assert!(!is_safe_sync("not json"));
+    }
+}
@@ -1,15 +1,35 @@
 use std::{env, path::PathBuf};
 
-use rusty_live_server::AsyncFileSystem;
+use rusty_live_server::{
+    ArchiveFileSystem, AsyncFileSystem, Cors, HeaderRule, Hook, Injection, Options,
+};
 
-const HELP: &str = "Usage: rusty-live-server PATH [OPTIONS]\n\nOptions:\n  -p, --port <PORT>  [default: 8080]\n  -h, --help         Print help\n  -V, --version      Print version";
+const HELP: &str = "Usage: rusty-live-server PATH [OPTIONS]\n\nPATH is a directory, or a .zip, .tar or .tar.gz file to serve without extracting it.\n\nOptions:\n  -p, --port <PORT>          [default: 8080]\n  -w, --watch <PATH>         Additional directory to watch for hooks\n  -x, --hook <GLOB=COMMAND>  Run COMMAND before reloading when a file matching GLOB changes\n  -c, --console              Print the browsers' console output\n      --console-log <FILE>   Also append the browsers' console output to FILE\n  -s, --sync                 Mirror scrolling, clicks and input between browsers\n      --no-preserve-scroll   Don't restore the scroll position after reloading\n      --preserve-forms       Restore form field values after reloading\n  -m, --morph                Morph the page instead of reloading when its HTML changes\n      --no-hot               Don't provide import.meta.hot to ES modules\n      --prefix <PATH>        URL path of the server's own endpoints [default: /__live]\n      --inject <POSITION>    Insert the reload script before </body>, </head> or not at all [body, head, none]\n      --csp-nonce <NONCE>    Nonce for the injected script tag\n      --csp-hash             Add an integrity hash to the injected script tag\n      --cors                 Allow cross-origin requests from any origin\n      --cors-origin <ORIGIN> Allow cross-origin requests with credentials from ORIGIN\n  -H, --header <GLOB=NAME: VALUE>  Add a response header to files matching GLOB\n      --cross-origin-isolated    Send COOP, COEP and CORP headers for SharedArrayBuffer\n      --no-clean-urls        Don't serve about.html for /about\n      --no-trailing-slash    Don't redirect directories to their URL with a trailing slash\n      --archives             Offer directories as ZIP and tar.gz downloads\n      --archive-limit <MB>   Largest directory to download as an archive [default: 1024]\n      --writable             Accept uploads, PUT, DELETE and MKCOL requests\n      --upload-limit <MB>    Largest upload with --writable [default: 256]\n      --webdav               Serve the directory over WebDAV at /__live/dav/ (webdav feature)\n  -h, --help                 Print help\n  -V, --version              Print version";
 
 #[tokio::main]
 async fn main() {
     match parse_args() {
-        Ok((path, port)) => {
-            let afs = AsyncFileSystem::default();
-            rusty_live_server::serve(path, port, true, None, afs)
+        Ok((path, port, options)) => {
+            if options.csp_hash {
+                println!(
+                    "Allow the reload script with: script-src '{}'",
+                    rusty_live_server::updater_hash()
+                );
+            }
+            // a file is served as an archive
+            if path.is_file() {
+                match ArchiveFileSystem::open(&path) {
+                    Ok(fs) => {
+                        rusty_live_server::serve_with_options(path, port, true, None, fs, options)
+                            .await
+                            .unwrap()
+                    }
+                    Err(e) => println!("error: {e}"),
+                }
+                return;
+            }
+            let afs = AsyncFileSystem;
+            rusty_live_server::serve_with_options(path, port, true, None, afs, options)
                 .await
                 .unwrap()
         }
@@ -19,45 +39,140 @@ async fn main() {
     }
 }
 
-fn parse_args() -> Result<(PathBuf, u16), &'static str> {
+fn parse_args() -> Result<(PathBuf, u16, Options), String> {
     let args = env::args().skip(1);
     let mut path = vec![];
     let mut port: u16 = 8080;
-    let mut next_port = false;
+    let mut options = Options::default();
+    let mut next: Option<&'static str> = None;
     for arg in args {
-        if let Some(arg) = arg.strip_prefix('-') {
-            if next_port {
-                return Err("error: a value is required for '--port <PORT>' but none was supplied");
+        if let (Some(name), true) = (next, arg.starts_with('-')) {
+            return Err(format!(
+                "error: a value is required for '{name}' but none was supplied"
+            ));
+        }
+        if let Some(name) = next.take() {
+            match name {
+                "--port <PORT>" => port = arg.parse().map_err(|_| "Invalid port")?,
+                "--watch <PATH>" => options.watch.push(PathBuf::from(arg)),
+                "--inject <POSITION>" => {
+                    options.injection = match arg.as_str() {
+                        "body" => Injection::Body,
+                        "head" => Injection::Head,
+                        "none" => Injection::Disabled,
+                        _ => return Err("error: expected one of body, head, none".to_string()),
+                    }
+                }
+                "--csp-nonce <NONCE>" => options.csp_nonce = Some(arg),
+                "--prefix <PATH>" => {
+                    if !arg.starts_with('/') {
+                        return Err("error: the prefix must start with /".to_string());
+                    }
+                    let prefix = arg.trim_end_matches('/');
+                    if prefix.is_empty() {
+                        return Err("error: the prefix can't be /".to_string());
+                    }
+                    options.prefix = prefix.to_string();
+                }
+                "--upload-limit <MB>" => {
+                    let limit: u64 = arg.parse().map_err(|_| "Invalid upload limit")?;
+                    options.upload_limit = limit
+                        .checked_mul(1024 * 1024)
+                        .ok_or("Upload limit too large")?;
+                }
+                "--archive-limit <MB>" => {
+                    let limit: u64 = arg.parse().map_err(|_| "Invalid archive limit")?;
+                    options.archive_limit = limit
+                        .checked_mul(1024 * 1024)
+                        .ok_or("Archive limit too large")?;
+                }
+                "--console-log <FILE>" => {
+                    options.console = true;
+                    options.console_log = Some(PathBuf::from(arg));
+                }
+                "--cors-origin <ORIGIN>" => {
+                    let cors = options.cors.get_or_insert_with(|| Cors {
+                        origins: vec![],
+                        credentials: true,
+                        ..Cors::default()
+                    });
+                    cors.origins.push(arg);
+                }
+                "--header <GLOB=NAME: VALUE>" => {
+                    let (pattern, header) = arg
+                        .split_once('=')
+                        .ok_or("error: expected '--header <GLOB=NAME: VALUE>'")?;
+                    let (name, value) = header
+                        .split_once(':')
+                        .ok_or("error: expected '--header <GLOB=NAME: VALUE>'")?;
+                    options.headers.push(HeaderRule {
+                        pattern: pattern.to_string(),
+                        name: name.trim().to_string(),
+                        value: value.trim().to_string(),
+                    });
+                }
+                "--hook <GLOB=COMMAND>" => {
+                    let (pattern, command) = arg
+                        .split_once('=')
+                        .ok_or("error: expected '--hook <GLOB=COMMAND>'")?;
+                    options.hooks.push(Hook {
+                        pattern: pattern.to_string(),
+                        command: command.to_string(),
+                    });
+                }
+                _ => unreachable!(),
             }
+        } else if let Some(arg) = arg.strip_prefix('-') {
             match arg {
-                "-port" | "p" => {
-                    next_port = true;
+                "-port" | "p" => next = Some("--port <PORT>"),
+                "-watch" | "w" => next = Some("--watch <PATH>"),
+                "-hook" | "x" => next = Some("--hook <GLOB=COMMAND>"),
+                "-console" | "c" => options.console = true,
+                "-sync" | "s" => options.sync = true,
+                "-no-preserve-scroll" => options.preserve_scroll = false,
+                "-preserve-forms" => options.preserve_forms = true,
+                "-morph" | "m" => options.morph = true,
+                "-no-hot" => options.hot = false,
+                "-inject" => next = Some("--inject <POSITION>"),
+                "-prefix" => next = Some("--prefix <PATH>"),
+                "-csp-nonce" => next = Some("--csp-nonce <NONCE>"),
+                "-csp-hash" => options.csp_hash = true,
+                "-no-clean-urls" => options.clean_urls = false,
+                "-no-trailing-slash" => options.trailing_slash = false,
+                "-archives" => options.archives = true,
+                "-writable" => options.writable = true,
+                #[cfg(feature = "webdav")]
+                "-webdav" => options.webdav = true,
+                "-upload-limit" => next = Some("--upload-limit <MB>"),
+                "-archive-limit" => next = Some("--archive-limit <MB>"),
+                "-cross-origin-isolated" => options.cross_origin_isolated = true,
+                "-cors" => options.cors = Some(Cors::default()),
+                "-cors-origin" => next = Some("--cors-origin <ORIGIN>"),
+                "-header" | "H" => next = Some("--header <GLOB=NAME: VALUE>"),
+                "-console-log" => next = Some("--console-log <FILE>"),
+                "-version" | "V" => {
+                    return Err(format!("rusty-live-server {}", env!("CARGO_PKG_VERSION")))
                 }
-                "-version" | "V" => return Err("rusty-live-server 0.4.0"),
-                "-help" | "h" => return Err(HELP),
+                "-help" | "h" => return Err(HELP.to_string()),
                 _ => {
-                    return Err("unkown argument");
+                    return Err("unkown argument".to_string());
                 }
             };
         } else {
-            match next_port {
-                true => {
-                    next_port = false;
-                    port = arg.parse().map_err(|_| "Invalid port")?;
-                }
-                false => path.push(arg),
-            }
+            path.push(arg);
         }
     }
-    if next_port {
-        return Err("error: a value is required for '--port <PORT>' but none was supplied");
+    if let Some(name) = next {
+        return Err(format!(
+            "error: a value is required for '{name}' but none was supplied"
+        ));
     }
     if path.is_empty() {
-        return Err(HELP);
+        return Err(HELP.to_string());
     }
     if path.len() > 1 {
-        return Err("error: more than 1 value provided");
+        return Err("error: more than 1 value provided".to_string());
     }
 
-    Ok((PathBuf::from(path.pop().unwrap()), port))
+    Ok((PathBuf::from(path.pop().unwrap()), port, options))
 }
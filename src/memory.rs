@@ -0,0 +1,318 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use crate::{Dir, Error, File, FileSystemInterface, Kind, Metadata, Signal};
+
+/// Files kept in memory, e.g. unsaved editor buffers or fixtures in tests.
+///
+/// Paths given to [`MemoryFileSystem::insert`], [`MemoryFileSystem::update`] and
+/// [`MemoryFileSystem::delete`] are relative to the served directory. Once the file
+/// system is served, they notify the browsers like changes on disk.
+#[derive(Clone, Default)]
+pub struct MemoryFileSystem {
+    inner: Arc<Mutex<Memory>>,
+}
+
+#[derive(Default)]
+struct Memory {
+    /// The path passed to [`crate::serve`], which all requested paths start with.
+    root: PathBuf,
+    /// Files and directories by relative path, except the root directory.
+    entries: BTreeMap<PathBuf, Entry>,
+    signal: Option<Arc<Signal>>,
+}
+
+#[derive(Clone)]
+enum Entry {
+    File {
+        contents: Arc<Vec<u8>>,
+        modified: SystemTime,
+    },
+    Dir {
+        modified: SystemTime,
+    },
+}
+
+impl MemoryFileSystem {
+    /// An empty file system served as `root`, which doesn't have to exist on disk.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let memory = Memory {
+            root: root.into(),
+            ..Default::default()
+        };
+        Self {
+            inner: Arc::new(Mutex::new(memory)),
+        }
+    }
+
+    /// Creates or replaces the file at `path`, along with missing parent directories.
+    /// Fails if `path` or one of its parents is a directory or file respectively.
+    pub fn insert(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> io::Result<()> {
+        let path = relative(path.as_ref()).ok_or_else(not_found)?;
+        self.inner.lock().unwrap().write(path, contents.into())
+    }
+
+    /// Replaces the contents of the existing file at `path`.
+    pub fn update(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> io::Result<()> {
+        let path = relative(path.as_ref()).ok_or_else(not_found)?;
+        let mut memory = self.inner.lock().unwrap();
+        match memory.entries.get(&path) {
+            Some(Entry::File { .. }) => memory.write(path, contents.into()),
+            _ => Err(not_found()),
+        }
+    }
+
+    /// Removes the file or directory with its contents at `path`.
+    pub fn delete(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = relative(path.as_ref()).ok_or_else(not_found)?;
+        self.inner.lock().unwrap().remove(&path)
+    }
+
+    /// The path of `path` inside the file system, or `None` outside of it.
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let memory = self.inner.lock().unwrap();
+        relative(path.strip_prefix(&memory.root).ok()?)
+    }
+
+    fn resolve_or_fail(&self, path: &Path) -> crate::Result<PathBuf> {
+        Ok(self.resolve(path).ok_or_else(not_found)?)
+    }
+}
+
+impl Memory {
+    fn kind(&self, path: &Path) -> Option<Kind> {
+        match self.entries.get(path) {
+            Some(Entry::File { .. }) => Some(Kind::File),
+            Some(Entry::Dir { .. }) => Some(Kind::Dir),
+            None if path.as_os_str().is_empty() => Some(Kind::Dir),
+            None => None,
+        }
+    }
+
+    fn write(&mut self, path: PathBuf, contents: Vec<u8>) -> io::Result<()> {
+        if path.as_os_str().is_empty() || self.kind(&path) == Some(Kind::Dir) {
+            return Err(io::Error::new(
+                io::ErrorKind::IsADirectory,
+                "a directory exists at the path",
+            ));
+        }
+        let parent = path.parent().unwrap_or(Path::new(""));
+        self.create_dirs(parent)?;
+        let modified = SystemTime::now();
+        let contents = Arc::new(contents);
+        let created = self
+            .entries
+            .insert(path.clone(), Entry::File { contents, modified })
+            .is_none();
+        if let Some(signal) = &self.signal {
+            if created {
+                signal.send_dir_changed(parent.to_path_buf());
+            }
+            signal.send_signal(path);
+        }
+        Ok(())
+    }
+
+    /// Creates `dir` and its missing parents.
+    fn create_dirs(&mut self, dir: &Path) -> io::Result<()> {
+        let mut path = PathBuf::new();
+        for segment in dir.iter() {
+            let parent = path.clone();
+            path.push(segment);
+            match self.kind(&path) {
+                Some(Kind::Dir) => continue,
+                Some(Kind::File) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotADirectory,
+                        "a file exists at a parent of the path",
+                    ))
+                }
+                None => {}
+            }
+            let modified = SystemTime::now();
+            self.entries.insert(path.clone(), Entry::Dir { modified });
+            if let Some(signal) = &self.signal {
+                signal.send_dir_changed(parent);
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &Path) -> io::Result<()> {
+        if self.entries.remove(path).is_none() {
+            return Err(not_found());
+        }
+        self.entries.retain(|key, _| !key.starts_with(path));
+        if let Some(signal) = &self.signal {
+            signal.send_dir_changed(path.parent().unwrap_or(Path::new("")).to_path_buf());
+        }
+        Ok(())
+    }
+}
+
+/// `path` without root, `.` and `..` components, which can't leave the file system.
+pub(crate) fn relative(path: &Path) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(v) => out.push(v),
+            Component::CurDir | Component::RootDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+pub(crate) fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no such file or directory")
+}
+
+struct MemoryDir {
+    entries: std::vec::IntoIter<PathBuf>,
+}
+
+impl Dir for MemoryDir {
+    async fn get_next(&mut self) -> crate::Result<Option<PathBuf>> {
+        Ok(self.entries.next())
+    }
+}
+
+struct MemoryFile {
+    contents: Arc<Vec<u8>>,
+}
+
+impl File for MemoryFile {
+    async fn read_to_end(&mut self) -> Vec<u8> {
+        self.contents.to_vec()
+    }
+}
+
+impl FileSystemInterface for MemoryFileSystem {
+    async fn get_file(&self, path: &Path) -> crate::Result<impl File> {
+        let path = self.resolve_or_fail(path)?;
+        match self.inner.lock().unwrap().entries.get(&path) {
+            Some(Entry::File { contents, .. }) => Ok(MemoryFile {
+                contents: contents.clone(),
+            }),
+            _ => Err(Error::Io(not_found())),
+        }
+    }
+
+    async fn get_dir(&self, path: &Path) -> crate::Result<impl Dir> {
+        let dir = self.resolve_or_fail(path)?;
+        let memory = self.inner.lock().unwrap();
+        if memory.kind(&dir) != Some(Kind::Dir) {
+            return Err(Error::Io(not_found()));
+        }
+        let entries: Vec<_> = memory
+            .entries
+            .keys()
+            .filter(|v| v.parent() == Some(&dir))
+            .map(|v| memory.root.join(v))
+            .collect();
+        Ok(MemoryDir {
+            entries: entries.into_iter(),
+        })
+    }
+
+    async fn metadata(&self, path: &Path) -> crate::Result<Metadata> {
+        let path = self.resolve_or_fail(path)?;
+        let memory = self.inner.lock().unwrap();
+        let metadata = match memory.entries.get(&path) {
+            Some(Entry::File { contents, modified }) => Metadata {
+                kind: Kind::File,
+                len: contents.len() as u64,
+                modified: Some(*modified),
+            },
+            Some(Entry::Dir { modified }) => Metadata {
+                kind: Kind::Dir,
+                len: 0,
+                modified: Some(*modified),
+            },
+            None if path.as_os_str().is_empty() => Metadata {
+                kind: Kind::Dir,
+                len: 0,
+                modified: None,
+            },
+            None => return Err(Error::Io(not_found())),
+        };
+        Ok(metadata)
+    }
+
+    async fn write_file(&self, path: &Path, contents: Vec<u8>) -> crate::Result<()> {
+        let path = self.resolve_or_fail(path)?;
+        let mut memory = self.inner.lock().unwrap();
+        if memory.kind(path.parent().unwrap_or(Path::new(""))) != Some(Kind::Dir) {
+            return Err(Error::Io(not_found()));
+        }
+        Ok(memory.write(path, contents)?)
+    }
+
+    async fn create_dir(&self, path: &Path) -> crate::Result<()> {
+        let path = self.resolve_or_fail(path)?;
+        let mut memory = self.inner.lock().unwrap();
+        if memory.kind(&path).is_some() {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "the path exists",
+            )));
+        }
+        if memory.kind(path.parent().unwrap_or(Path::new(""))) != Some(Kind::Dir) {
+            return Err(Error::Io(not_found()));
+        }
+        Ok(memory.create_dirs(&path)?)
+    }
+
+    async fn remove(&self, path: &Path) -> crate::Result<()> {
+        let path = self.resolve_or_fail(path)?;
+        Ok(self.inner.lock().unwrap().remove(&path)?)
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> crate::Result<()> {
+        let (from, to) = (self.resolve_or_fail(from)?, self.resolve_or_fail(to)?);
+        let mut memory = self.inner.lock().unwrap();
+        let to_parent = to.parent().unwrap_or(Path::new(""));
+        if from.as_os_str().is_empty()
+            || memory.kind(&from).is_none()
+            || memory.kind(&to).is_some()
+            || memory.kind(to_parent) != Some(Kind::Dir)
+            || to.starts_with(&from)
+        {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "can't move the path there",
+            )));
+        }
+        let moved: Vec<_> = memory
+            .entries
+            .keys()
+            .filter(|v| v.starts_with(&from))
+            .cloned()
+            .collect();
+        for key in moved {
+            if let Some(entry) = memory.entries.remove(&key) {
+                let target = match key.strip_prefix(&from) {
+                    Ok(rest) if !rest.as_os_str().is_empty() => to.join(rest),
+                    _ => to.clone(),
+                };
+                memory.entries.insert(target, entry);
+            }
+        }
+        if let Some(signal) = &memory.signal {
+            signal.send_dir_changed(from.parent().unwrap_or(Path::new("")).to_path_buf());
+            signal.send_dir_changed(to_parent.to_path_buf());
+            signal.send_signal(to);
+        }
+        Ok(())
+    }
+
+    fn report_changes(&self, signal: Arc<Signal>) -> bool {
+        self.inner.lock().unwrap().signal = Some(signal);
+        true
+    }
+}
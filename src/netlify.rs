@@ -0,0 +1,336 @@
+use std::{path::Path, sync::Arc};
+
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    fs::File as _,
+    http::{percent_encode, Response},
+    signal::Event,
+    Context, FileSystemInterface,
+};
+
+const HEADERS: &str = "_headers";
+const REDIRECTS: &str = "_redirects";
+
+/// Rules from the `_headers` and `_redirects` files in the served directory, in the
+/// format static hosts like Netlify read them.
+#[derive(Debug, Default)]
+pub struct Rules {
+    redirects: Vec<Redirect>,
+    headers: Vec<(String, Vec<(String, String)>)>,
+}
+
+#[derive(Debug)]
+struct Redirect {
+    from: String,
+    /// `key=:placeholder` query parameters that must be present.
+    query: Vec<(String, String)>,
+    to: String,
+    status: u16,
+    /// Also applies when a file exists at `from`.
+    force: bool,
+}
+
+/// What a matching redirect rule does with a request.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Respond with `status` and `Location: url`.
+    Redirect { status: u16, url: String },
+    /// Serve the file at `path` with `status`, either 200 or an error page.
+    Rewrite { status: u16, path: String },
+}
+
+impl Rules {
+    /// Reads both files from `base_dir`. Missing files yield no rules.
+    pub async fn load(fs: &impl FileSystemInterface, base_dir: &Path) -> Self {
+        let read = |name| async move {
+            match fs.get_file(&base_dir.join(name)).await {
+                Ok(mut file) => String::from_utf8_lossy(&file.read_to_end().await).into_owned(),
+                Err(_) => String::new(),
+            }
+        };
+        Self {
+            redirects: parse_redirects(&read(REDIRECTS).await),
+            headers: parse_headers(&read(HEADERS).await),
+        }
+    }
+
+    /// The first redirect matching `path`. Unless forced, rules don't shadow a file
+    /// that `exists`.
+    pub fn redirect(&self, path: &str, query: &[(String, String)], exists: bool) -> Option<Action> {
+        self.redirects.iter().find_map(|rule| {
+            if exists && !rule.force {
+                return None;
+            }
+            let mut params = matches(&rule.from, path)?;
+            for (key, placeholder) in &rule.query {
+                let (_, value) = query.iter().find(|(k, _)| k == key)?;
+                // query values are decoded, unlike the path, and end up in a header
+                if let Some(name) = placeholder.strip_prefix(':') {
+                    params.push((name.to_string(), percent_encode(value)));
+                }
+            }
+            let to = substitute(&rule.to, params);
+            match rule.status {
+                200 | 404 | 410 | 451 if to.starts_with('/') => Some(Action::Rewrite {
+                    status: rule.status,
+                    path: to.split('?').next().unwrap_or_default().to_string(),
+                }),
+                // proxying to another host isn't supported
+                200 => None,
+                status => Some(Action::Redirect { status, url: to }),
+            }
+        })
+    }
+
+    /// Sets the headers of every block matching `path`.
+    pub fn apply_headers(&self, path: &str, response: &mut Response) {
+        for (pattern, headers) in &self.headers {
+            if matches(pattern, path).is_some() {
+                for (name, value) in headers {
+                    response.set_header(name, value);
+                }
+            }
+        }
+    }
+}
+
+/// Reloads the rules whenever one of the files changes, or the served directory's
+/// entries change, since removing a file only reports the latter.
+pub async fn watch<T: FileSystemInterface>(ctx: Arc<Context<T>>) {
+    let mut events = ctx.signal.subscribe();
+    loop {
+        let reload = match events.recv().await {
+            Ok(Event::Changed(path)) => path == Path::new(HEADERS) || path == Path::new(REDIRECTS),
+            Ok(Event::DirChanged(dir)) => dir.as_os_str().is_empty(),
+            Ok(_) | Err(RecvError::Lagged(_)) => false,
+            Err(RecvError::Closed) => break,
+        };
+        if reload {
+            let loaded = Rules::load(&ctx.fs, &ctx.base_dir).await;
+            *ctx.rules.lock().unwrap() = Arc::new(loaded);
+        }
+    }
+}
+
+/// Lines of `from [key=:value ...] to [status][!]`, `#` starts a comment.
+fn parse_redirects(text: &str) -> Vec<Redirect> {
+    let mut redirects = vec![];
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut parts = line.split_whitespace();
+        let Some(from) = parts.next().filter(|v| v.starts_with('/')) else {
+            continue;
+        };
+        let mut query = vec![];
+        let mut to = None;
+        for part in parts.by_ref() {
+            match part.split_once('=') {
+                Some((key, value)) if !part.starts_with('/') && !part.contains("://") => {
+                    query.push((key.to_string(), value.to_string()))
+                }
+                _ => {
+                    to = Some(part);
+                    break;
+                }
+            }
+        }
+        let Some(to) = to else {
+            continue;
+        };
+        let (status, force) = match parts.next() {
+            Some(status) => match status.strip_suffix('!') {
+                Some(status) => (status, true),
+                None => (status, false),
+            },
+            None => ("301", false),
+        };
+        let Some(status) = status.parse().ok().filter(|v| status_line(*v).is_some()) else {
+            #[cfg(feature = "log")]
+            log::warn!("{REDIRECTS}: unsupported status in {line:?}");
+            continue;
+        };
+        redirects.push(Redirect {
+            from: from.to_string(),
+            query,
+            to: to.to_string(),
+            status,
+            force,
+        });
+    }
+    redirects
+}
+
+/// Unindented path patterns, each followed by indented `Name: value` lines.
+fn parse_headers(text: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut blocks: Vec<(String, Vec<(String, String)>)> = vec![];
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            blocks.push((trimmed.to_string(), vec![]));
+        } else if let (Some((_, headers)), Some((name, value))) =
+            (blocks.last_mut(), trimmed.split_once(':'))
+        {
+            let (name, value) = (name.trim(), value.trim());
+            match headers
+                .iter_mut()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            {
+                Some((_, existing)) => *existing = format!("{existing}, {value}"),
+                None => headers.push((name.to_string(), value.to_string())),
+            }
+        }
+    }
+    blocks.retain(|(pattern, _)| pattern.starts_with('/'));
+    blocks
+}
+
+/// Matches `path` against a pattern like `/blog/:year/*` or `/assets/*.css`, returning
+/// the values of the placeholders and of the splat. A `*` in the last segment also
+/// matches further segments.
+fn matches(pattern: &str, path: &str) -> Option<Vec<(String, String)>> {
+    let pattern: Vec<_> = pattern.trim_end_matches('/').split('/').collect();
+    let path: Vec<_> = path.trim_end_matches('/').split('/').collect();
+    let mut params = vec![];
+    for (i, segment) in pattern.iter().enumerate() {
+        let last = i == pattern.len() - 1;
+        if *segment == "*" && last {
+            let splat = path.get(i..).unwrap_or_default().join("/");
+            params.push(("splat".to_string(), splat));
+            return Some(params);
+        }
+        if let Some((head, tail)) = segment.split_once('*') {
+            let value = match last {
+                true => path.get(i..).unwrap_or_default().join("/"),
+                false => path.get(i)?.to_string(),
+            };
+            let splat = value
+                .strip_prefix(head)?
+                .strip_suffix(tail)
+                .filter(|_| value.len() >= head.len() + tail.len())?;
+            params.push(("splat".to_string(), splat.to_string()));
+            if last {
+                return Some(params);
+            }
+            continue;
+        }
+        let value = path.get(i)?;
+        match segment.strip_prefix(':') {
+            Some(name) => params.push((name.to_string(), value.to_string())),
+            None if segment == value => {}
+            None => return None,
+        }
+    }
+    (pattern.len() == path.len()).then_some(params)
+}
+
+/// Replaces `:name` in `to`, longer names first so `:id` doesn't clobber `:idx`.
+fn substitute(to: &str, mut params: Vec<(String, String)>) -> String {
+    params.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+    let mut to = to.to_string();
+    for (name, value) in params {
+        to = to.replace(&format!(":{name}"), &value);
+    }
+    to
+}
+
+/// Status lines for the codes a redirect rule may use.
+pub fn status_line(status: u16) -> Option<&'static str> {
+    Some(match status {
+        200 => "200 OK",
+        301 => "301 Moved Permanently",
+        302 => "302 Found",
+        303 => "303 See Other",
+        307 => "307 Temporary Redirect",
+        308 => "308 Permanent Redirect",
+        404 => "404 NOT FOUND",
+        410 => "410 Gone",
+        451 => "451 Unavailable For Legal Reasons",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pattern: &str, path: &str) -> Option<Vec<(String, String)>> {
+        matches(pattern, path)
+    }
+
+    fn splat(value: &str) -> Option<Vec<(String, String)>> {
+        Some(vec![("splat".to_string(), value.to_string())])
+    }
+
+    #[test]
+    fn placeholders_and_trailing_splat() {
+        assert_eq!(
+            params("/blog/:year/:slug", "/blog/2024/hello/"),
+            Some(vec![
+                ("year".to_string(), "2024".to_string()),
+                ("slug".to_string(), "hello".to_string()),
+            ])
+        );
+        assert_eq!(params("/blog/:year", "/blog/2024/hello"), None);
+        assert_eq!(params("/docs/*", "/docs/a/b.html"), splat("a/b.html"));
+        assert_eq!(params("/docs/*", "/docs"), splat(""));
+        assert_eq!(params("/docs/*", "/other/a"), None);
+    }
+
+    #[test]
+    fn wildcards_inside_segments() {
+        assert_eq!(params("/*.wasm", "/pkg/app.wasm"), splat("pkg/app"));
+        assert_eq!(params("/*.wasm", "/app.js"), None);
+        assert_eq!(params("/assets/*.css", "/assets/site.css"), splat("site"));
+        assert_eq!(params("/assets/*.css", "/assets/site.js"), None);
+        assert_eq!(params("/v*/api", "/v2/api"), splat("2"));
+        assert_eq!(params("/v*/api", "/v2/other"), None);
+        assert_eq!(params("/a*a", "/a"), None);
+    }
+
+    #[test]
+    fn redirect_lines() {
+        let text = "# comment\n/old /new\n/api/* https://example.com/:splat 200\n\
+/search q=:q /find/:q 302!\n/bad /x 999\nnot-a-path /x\n";
+        let redirects = parse_redirects(text);
+        assert_eq!(redirects.len(), 3);
+        assert_eq!(
+            (redirects[0].from.as_str(), redirects[0].to.as_str()),
+            ("/old", "/new")
+        );
+        assert_eq!((redirects[0].status, redirects[0].force), (301, false));
+        assert_eq!(redirects[1].status, 200);
+        assert_eq!(
+            redirects[2].query,
+            vec![("q".to_string(), ":q".to_string())]
+        );
+        assert_eq!((redirects[2].status, redirects[2].force), (302, true));
+
+        let rules = Rules {
+            redirects,
+            headers: vec![],
+        };
+        let query = [("q".to_string(), "rust".to_string())];
+        assert_eq!(
+            rules.redirect("/search", &query, false),
+            Some(Action::Redirect {
+                status: 302,
+                url: "/find/rust".to_string()
+            })
+        );
+        let query = [("q".to_string(), "a b\r\nSet-Cookie: x=1".to_string())];
+        assert_eq!(
+            rules.redirect("/search", &query, false),
+            Some(Action::Redirect {
+                status: 302,
+                url: "/find/a%20b%0D%0ASet-Cookie%3A%20x%3D1".to_string()
+            })
+        );
+        // proxying isn't supported, and unforced rules don't shadow files
+        assert_eq!(rules.redirect("/api/users", &[], false), None);
+        assert_eq!(rules.redirect("/old", &[], true), None);
+    }
+}
@@ -0,0 +1,229 @@
+use std::path::PathBuf;
+
+/// Runs `command` whenever a file matching `pattern` changes.
+///
+/// Patterns are globs relative to the current working directory, see [`crate::serve_with_options`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    pub pattern: String,
+    pub command: String,
+}
+
+/// Cross-origin resource sharing for the served files and the server's endpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cors {
+    /// Origins allowed to read responses, `*` allows any origin.
+    pub origins: Vec<String>,
+    /// Methods allowed by a preflight.
+    pub methods: Vec<String>,
+    /// Request headers allowed by a preflight. If empty, the requested headers are allowed.
+    pub headers: Vec<String>,
+    /// Allow cookies and authorization. The request's origin is echoed instead of `*`.
+    pub credentials: bool,
+    /// Seconds a browser may cache a preflight.
+    pub max_age: Option<u32>,
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Self {
+            origins: vec!["*".to_string()],
+            methods: ["GET", "HEAD", "POST", "OPTIONS"]
+                .map(String::from)
+                .to_vec(),
+            headers: vec![],
+            credentials: false,
+            max_age: Some(86400),
+        }
+    }
+}
+
+/// Adds the header `name: value` to responses for paths matching `pattern`.
+///
+/// Patterns are globs relative to the served directory, e.g. `**/*.wasm`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderRule {
+    pub pattern: String,
+    pub name: String,
+    pub value: String,
+}
+
+/// Where the live-reload script is added to HTML pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Injection {
+    /// Before `</body>`.
+    #[default]
+    Body,
+    /// Before `</head>`.
+    Head,
+    /// Pages are served unchanged and don't reload.
+    Disabled,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Commands that run before reloading. Matching hooks run sequentially; a newer
+    /// change cancels a running hook and starts over. A failing hook is reported as
+    /// a build error instead of reloading.
+    pub hooks: Vec<Hook>,
+    /// Additional directories to watch. Changes in them only trigger hooks.
+    pub watch: Vec<PathBuf>,
+    /// Forward `console.*` calls, uncaught errors and unhandled rejections from the
+    /// browser and print them together with the client's address and user agent.
+    pub console: bool,
+    /// Also append forwarded console messages to this file.
+    pub console_log: Option<PathBuf>,
+    /// Mirror scrolling, clicks, navigation and form input between all clients that
+    /// show the same page.
+    pub sync: bool,
+    /// Restore the scroll position of the window and of scrolled elements with an
+    /// `id` after a full reload.
+    pub preserve_scroll: bool,
+    /// Restore the values of form fields after a full reload.
+    pub preserve_forms: bool,
+    /// Send changed HTML pages to the browser and morph the live DOM toward them
+    /// instead of reloading. Changes to `<head>` still reload.
+    pub morph: bool,
+    /// Provide `import.meta.hot` to ES modules. A changed module that called
+    /// `import.meta.hot.accept()` is imported again instead of reloading the page.
+    pub hot: bool,
+    /// URL path below which the server's own endpoints live, e.g. the reload socket at
+    /// `/__live/ws` and the reload script at `/__live/updater.js`. Files below it are
+    /// not served. A missing leading `/` is added, and an empty prefix or `/` falls
+    /// back to `/__live`.
+    pub prefix: String,
+    /// Where the `<script src="/__live/updater.js">` tag is inserted into HTML pages.
+    pub injection: Injection,
+    /// Nonce for the injected script tag. Without one the nonce of the page's first
+    /// script is reused.
+    pub csp_nonce: Option<String>,
+    /// Add an `integrity` attribute to the injected script tag, so a CSP can allow it
+    /// by hash, see [`crate::updater_hash`].
+    pub csp_hash: bool,
+    /// Answer preflights and add `Access-Control-Allow-*` headers.
+    pub cors: Option<Cors>,
+    /// Extra response headers. Later rules override earlier ones.
+    pub headers: Vec<HeaderRule>,
+    /// Send `Cross-Origin-Opener-Policy`, `Cross-Origin-Embedder-Policy` and
+    /// `Cross-Origin-Resource-Policy` with every response, so pages can use
+    /// `SharedArrayBuffer`, e.g. for WebAssembly threads.
+    pub cross_origin_isolated: bool,
+    /// Serve `about.html` for `/about` when no file `about` exists.
+    pub clean_urls: bool,
+    /// Redirect directory requests without a trailing slash to the URL with one, so
+    /// relative links on the listing or `index.html` resolve inside the directory.
+    pub trailing_slash: bool,
+    /// Offer directories for download as `?download=zip` or `?download=tar.gz`. Off by
+    /// default, since anyone who can reach the server could download them.
+    pub archives: bool,
+    /// Largest total size in bytes of the files in a downloaded archive.
+    pub archive_limit: u64,
+    /// Accept `PUT`, `DELETE` and `MKCOL` requests and uploads from the directory
+    /// listing, which change the served files.
+    pub writable: bool,
+    /// Largest request body in bytes when [`Options::writable`] is set.
+    pub upload_limit: u64,
+    /// Serve the directory over WebDAV below `{prefix}/dav/`, e.g. to mount it in a
+    /// file manager. Uses [`Options::upload_limit`] too.
+    #[cfg(feature = "webdav")]
+    pub webdav: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            hooks: vec![],
+            watch: vec![],
+            console: false,
+            console_log: None,
+            sync: false,
+            preserve_scroll: true,
+            preserve_forms: false,
+            morph: false,
+            hot: true,
+            prefix: "/__live".to_string(),
+            injection: Injection::default(),
+            csp_nonce: None,
+            csp_hash: false,
+            cors: None,
+            headers: vec![],
+            cross_origin_isolated: false,
+            clean_urls: true,
+            trailing_slash: true,
+            archives: false,
+            archive_limit: 1024 * 1024 * 1024,
+            writable: false,
+            upload_limit: 256 * 1024 * 1024,
+            #[cfg(feature = "webdav")]
+            webdav: false,
+        }
+    }
+}
+
+impl Options {
+    /// URL path of the internal endpoint `name`.
+    pub(crate) fn endpoint(&self, name: &str) -> String {
+        format!("{}/{}", self.prefix(), name)
+    }
+
+    /// The endpoint name if `path` is below [`Options::prefix`].
+    pub(crate) fn internal_endpoint<'a>(&self, path: &'a str) -> Option<&'a str> {
+        path.strip_prefix(&self.prefix())?.strip_prefix('/')
+    }
+
+    /// [`Options::prefix`] with a leading and without a trailing `/`.
+    fn prefix(&self) -> String {
+        match self.prefix.trim_matches('/') {
+            "" => "/__live".to_string(),
+            prefix => format!("/{prefix}"),
+        }
+    }
+
+    /// Whether requests may change the served files, which allows larger bodies.
+    pub(crate) fn accepts_changes(&self) -> bool {
+        #[cfg(feature = "webdav")]
+        if self.webdav {
+            return true;
+        }
+        self.writable
+    }
+
+    /// Settings sent to `updater.js` when the client `id` connects.
+    pub(crate) fn client_config(&self, id: u64) -> String {
+        format!(
+            "{{\"id\":{},\"console\":{},\"sync\":{},\"preserveScroll\":{},\"preserveForms\":{},\"morph\":{}}}",
+            id, self.console, self.sync, self.preserve_scroll, self.preserve_forms, self.morph
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_prefix(prefix: &str) -> Options {
+        Options {
+            prefix: prefix.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn endpoints_below_the_prefix() {
+        for prefix in ["/dev/live", "/dev/live/", "dev/live"] {
+            let options = with_prefix(prefix);
+            assert_eq!(options.endpoint("ws"), "/dev/live/ws");
+            assert_eq!(options.internal_endpoint("/dev/live/ws"), Some("ws"));
+            assert_eq!(options.internal_endpoint("/dev/lively"), None);
+        }
+    }
+
+    #[test]
+    fn empty_prefix_falls_back_to_the_default() {
+        for prefix in ["", "/"] {
+            let options = with_prefix(prefix);
+            assert_eq!(options.endpoint("ws"), "/__live/ws");
+            assert_eq!(options.internal_endpoint("/index.html"), None);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use tokio::{fs::OpenOptions, io::AsyncWriteExt as _};
+
+use crate::{clients::Client, Options};
+
+/// Prints a `level:text` entry forwarded from a browser's console.
+pub async fn print(options: &Options, client: &Client, entry: &str) {
+    if !options.console {
+        return;
+    }
+    let (level, text) = entry.split_once(':').unwrap_or(("log", entry));
+    let line = format!(
+        "[{} {}] {}: {}",
+        client.addr, client.user_agent, level, text
+    );
+    match level {
+        "error" | "warn" => eprintln!("{line}"),
+        _ => println!("{line}"),
+    }
+    if let Some(path) = &options.console_log {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await;
+        match file {
+            Ok(mut file) => {
+                let _ = file.write_all(format!("{line}\n").as_bytes()).await;
+            }
+            Err(_e) => {
+                #[cfg(feature = "log")]
+                log::warn!("Error writing console log: {:?}", _e);
+            }
+        }
+    }
+}
@@ -1,193 +1,356 @@
 use std::{
+    net::SocketAddr,
     path::{Path, PathBuf},
     sync::Arc,
 };
 
-use tokio::{
-    io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader},
-    net::TcpStream,
-};
+use tokio::{io::BufReader, net::TcpStream};
 
-use crate::{fs::File, websocket::handle_websocket, Dir, FileSystemInterface, Signal};
+#[cfg(feature = "webdav")]
+use crate::webdav;
+use crate::{
+    archive::{self, Format},
+    clients::Client,
+    fs::{File, Kind},
+    headers,
+    http::{percent_decode, read_request, Request, Response, MAX_BODY},
+    inject::{inject, inject_hot, is_html, is_javascript, HOT_JS, UPDATER_JS},
+    listing::{self, View},
+    live,
+    netlify::{status_line, Action},
+    sse::handle_events,
+    upload,
+    websocket::handle_websocket,
+    BuildError, Context, FileSystemInterface, Options,
+};
 
-pub async fn handle_client(
+pub async fn handle_client<T: FileSystemInterface + 'static>(
     mut stream: TcpStream,
-    base_dir: PathBuf,
-    signal: Arc<Signal>,
-    fs: impl FileSystemInterface,
+    addr: SocketAddr,
+    ctx: Arc<Context<T>>,
 ) {
     let mut reader = BufReader::new(&mut stream);
-    let mut request = String::new();
-    let mut line = String::new();
-
-    loop {
-        let bytes_read = reader.read_line(&mut line).await;
-        let bytes_read = match bytes_read {
-            Ok(v) => v,
-            Err(_) => {
-                break;
+    let max_body = match ctx.options.accepts_changes() {
+        true => ctx.options.upload_limit as usize,
+        false => MAX_BODY,
+    };
+    let request = match read_request(&mut reader, max_body).await {
+        Some(v) => v,
+        None => return,
+    };
+    drop(reader);
+    let endpoint = ctx.options.internal_endpoint(&request.path);
+    if request.method == "GET" {
+        match (endpoint, request.header("Sec-WebSocket-Key")) {
+            (Some("ws"), Some(key)) => {
+                let client = new_client(&request, addr);
+                let _ = handle_websocket(stream, key.to_string(), ctx, client).await;
+                return;
             }
-        };
-        if bytes_read == 0 {
-            break;
+            (Some("events"), _) => {
+                let client = new_client(&request, addr);
+                let _ = handle_events(stream, &request, ctx, client).await;
+                return;
+            }
+            _ => {}
+        }
+    }
+    if let (true, None, Some(format)) = (
+        ctx.options.archives && request.method == "GET",
+        endpoint,
+        Format::requested(&request),
+    ) {
+        let dir = local_path(&ctx, &request.path);
+        if ctx.fs.kind(&dir).await == Some(Kind::Dir) {
+            let _ = archive::serve(&mut stream, &request, &dir, format, &ctx).await;
+            return;
         }
+    }
+    let mut response = match (request.method.as_str(), endpoint) {
+        _ if has_effects(&request, endpoint, &ctx.options)
+            && !headers::is_same_origin(&ctx.options, &request) =>
+        {
+            Response::new("403 Forbidden")
+        }
+        #[cfg(feature = "webdav")]
+        (_, Some(endpoint)) if ctx.options.webdav && webdav::is_dav(endpoint) => {
+            webdav::handle(&request, endpoint, &ctx).await
+        }
+        ("OPTIONS", _) => headers::preflight(&ctx.options, &request),
+        (_, Some(endpoint)) => handle_endpoint(&request, endpoint, &ctx).await,
+        ("GET" | "HEAD", None) => handle_file(&request, &ctx).await,
+        ("PUT" | "DELETE" | "MKCOL" | "POST", None) if ctx.options.writable => {
+            upload::handle(&request, &local_path(&ctx, &request.path), &ctx).await
+        }
+        _ => Response::new("405 Method Not Allowed")
+            .with_header("Allow", headers::allow(&ctx.options)),
+    };
+    headers::apply(&ctx.options, &request, &mut response);
+    let _ = response.write(&mut stream, request.method == "HEAD").await;
+}
 
-        request.push_str(&line);
-        line.clear();
-        if request.contains("\r\n\r\n") {
-            break;
+/// Whether the request may change the served files through [`upload`] or WebDAV, or
+/// reach the developer through the console and error overlay endpoints.
+fn has_effects(request: &Request, endpoint: Option<&str>, options: &Options) -> bool {
+    let method = request.method.as_str();
+    match endpoint {
+        #[cfg(feature = "webdav")]
+        Some(endpoint) if options.webdav && webdav::is_dav(endpoint) => {
+            !matches!(method, "GET" | "HEAD" | "OPTIONS" | "PROPFIND")
         }
+        Some(endpoint) => method == "POST" && matches!(endpoint, "message" | "error"),
+        None => options.writable && matches!(method, "PUT" | "DELETE" | "MKCOL" | "POST"),
+    }
+}
+
+async fn handle_file(request: &Request, ctx: &Context<impl FileSystemInterface>) -> Response {
+    let path = request.path.as_str();
+    if let Some(page) = referring_page(request) {
+        ctx.clients.record(page, path);
     }
-    let mut parts = request.split_whitespace();
-    let protocol = parts.next();
-    let temp = (protocol, parts.next(), parts.next());
-    if let (Some(method), Some(path), Some(_)) = temp {
-        if matches!(method, "GET" | "HEAD") {
-            let head = method == "HEAD";
-            let mut file_path = base_dir.to_path_buf();
-            let mut websocket = None;
-            let path = path.split_once('?').map(|v| v.0).unwrap_or(path);
-            if path != "/" {
-                file_path.push(&path[1..]);
+    let rules = ctx.rules.lock().unwrap().clone();
+    let file_path = local_path(ctx, path);
+    let exists =
+        ctx.fs.exists(&file_path).await || clean_url(ctx, path, &file_path).await.is_some();
+    let mut response = match rules.redirect(path, &request.query_pairs(), exists) {
+        Some(Action::Redirect { status, url }) => redirect(
+            status_line(status).unwrap_or("302 Found"),
+            url,
+            request.query.as_deref(),
+        ),
+        Some(Action::Rewrite { status, path }) => {
+            let mut response = serve_path(request, &path, ctx).await;
+            if response.status == "200 OK" {
+                response.status = status_line(status).unwrap_or(response.status);
             }
-            if path == "/ws" {
-                while let Some(header) = parts.next() {
-                    if header == "Sec-WebSocket-Key:" {
-                        if let Some(next_header) = parts.next() {
-                            websocket = Some(next_header.to_string());
-                        }
-                        break;
-                    }
-                }
+            response
+        }
+        None => serve_path(request, path, ctx).await,
+    };
+    let page = response
+        .header("Content-Type")
+        .is_some_and(|v| v.starts_with("text/html"));
+    if page && request.method == "GET" {
+        ctx.clients.loaded(path);
+    }
+    rules.apply_headers(path, &mut response);
+    response
+}
+
+/// The file for the URL path `path`. `..` segments are dropped, so it stays inside
+/// the served directory.
+pub fn local_path(ctx: &Context<impl FileSystemInterface>, path: &str) -> PathBuf {
+    let mut file_path = ctx.base_dir.to_path_buf();
+    for segment in percent_decode(path, false).split(['/', '\\']) {
+        if !matches!(segment, "" | "." | "..") {
+            file_path.push(segment);
+        }
+    }
+    file_path
+}
+
+async fn serve_path(
+    request: &Request,
+    path: &str,
+    ctx: &Context<impl FileSystemInterface>,
+) -> Response {
+    let file_path = local_path(ctx, path);
+    let found = ctx.fs.kind(&file_path).await;
+    let response = if path == "/favicon.ico" {
+        Ok(serve_favicon(&file_path, &ctx.fs).await)
+    } else if found == Some(Kind::File) {
+        serve_file(path, &file_path, ctx).await
+    } else if let Some(html) = clean_url(ctx, path, &file_path).await {
+        serve_file(&format!("{path}.html"), &html, ctx).await
+    } else if found == Some(Kind::Dir) {
+        match ctx.options.trailing_slash && !path.ends_with('/') {
+            true => Ok(redirect(
+                "301 Moved Permanently",
+                format!("{path}/"),
+                request.query.as_deref(),
+            )),
+            false => serve_directory(request, path, &file_path, ctx).await,
+        }
+    } else {
+        Ok(serve_404())
+    };
+    response.unwrap_or_else(|_| serve_500())
+}
+
+/// `about.html` for a request of `/about`, if [`crate::Options::clean_urls`] is set.
+async fn clean_url(
+    ctx: &Context<impl FileSystemInterface>,
+    path: &str,
+    file_path: &Path,
+) -> Option<PathBuf> {
+    if !ctx.options.clean_urls || path.ends_with('/') {
+        return None;
+    }
+    let mut html = file_path.as_os_str().to_owned();
+    html.push(".html");
+    let html = PathBuf::from(html);
+    (ctx.fs.kind(&html).await == Some(Kind::File)).then_some(html)
+}
+
+/// Redirects to `url`, keeping the request's `query` unless `url` has its own.
+fn redirect(status: &'static str, mut url: String, query: Option<&str>) -> Response {
+    if let (false, Some(query)) = (url.contains('?'), query) {
+        url = format!("{url}?{query}");
+    }
+    Response::new(status).with_header("Location", url)
+}
+
+/// Requests below [`crate::Options::prefix`], which never reach the served files.
+async fn handle_endpoint(
+    request: &Request,
+    endpoint: &str,
+    ctx: &Context<impl FileSystemInterface>,
+) -> Response {
+    match (request.method.as_str(), endpoint) {
+        ("GET" | "HEAD", "updater.js") => serve_script(UPDATER_JS),
+        ("GET" | "HEAD", "hot.js") => serve_script(HOT_JS),
+        ("GET" | "HEAD", "favicon.ico") => favicon_response(FAVICON.to_vec()),
+        ("POST", "ping") => Response::ok("text", b"pong".to_vec()),
+        ("POST", "error") => {
+            match parse_build_error(request) {
+                Some(error) => ctx.signal.send_error(error),
+                None => ctx.signal.clear_error(),
             }
-            if let Some(key) = websocket {
-                let _ = handle_websocket(stream, key, signal).await;
-            } else if path == "/favicon.ico" {
-                serve_favicon(&file_path, &mut stream, fs, head).await;
-            } else if file_path.is_dir() {
-                if serve_directory(&file_path, &mut stream, fs, head)
-                    .await
-                    .is_err()
-                {
-                    serve_500(&mut stream).await;
-                }
-            } else if file_path.is_file() {
-                if serve_file(&file_path, &mut stream, fs, head).await.is_err() {
-                    serve_500(&mut stream).await;
+            Response::new("204 No Content")
+        }
+        // browsers connected over Server-Sent Events post their messages here
+        ("POST", "message") => {
+            let client = request
+                .query_pairs()
+                .into_iter()
+                .find(|(key, _)| key == "client")
+                .and_then(|(_, value)| value.parse().ok())
+                .and_then(|id| ctx.clients.get(id));
+            match client {
+                Some(client) => {
+                    live::receive(ctx, &client, &String::from_utf8_lossy(&request.body)).await;
+                    Response::new("204 No Content")
                 }
-            } else {
-                serve_404(&mut stream).await;
+                None => serve_404(),
             }
         }
-    } else if let (Some("POST"), Some("/ping"), Some(_)) = temp {
-        let contents = "pong";
-        let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text\r\n\r\n{}", contents);
-        let _ = stream.write(response.as_bytes()).await;
+        _ => serve_404(),
     }
 }
 
-async fn serve_directory(
-    dir: &Path,
-    stream: &mut TcpStream,
-    fs: impl FileSystemInterface,
-    head: bool,
-) -> crate::Result<()> {
-    let mut response = String::new();
-    response.push_str("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nConnection: close\r\n\r\n");
-    if head {
-        let _ = stream.write(response.as_bytes()).await?;
-        return Ok(());
-    }
-    response.push_str("<html><body><ul>");
-    let mut entries = fs.get_dir(dir).await?;
-    let mut found_index = None;
-    while let Ok(Some(entry)) = entries.get_next().await {
-        let file_name = entry
-            .file_name()
-            .and_then(|v| v.to_str())
-            .unwrap_or_default();
-        if file_name == "index.html" {
-            found_index = Some(dir.join("index.html"));
-            break;
+/// A client connecting to the live-reload endpoint, which passes its page as `?page=`.
+fn new_client(request: &Request, addr: SocketAddr) -> Client {
+    let page = request
+        .query_pairs()
+        .into_iter()
+        .find(|(key, _)| key == "page")
+        .map(|(_, value)| value)
+        .unwrap_or_default();
+    let user_agent = request.header("User-Agent").unwrap_or_default();
+    Client::new(addr, user_agent.to_string(), page)
+}
+
+/// Path of the page that requested a resource, from a same-origin `Referer`.
+fn referring_page(request: &Request) -> Option<&str> {
+    let referer = request.header("Referer")?;
+    let (_, rest) = referer.split_once("://")?;
+    let (host, page) = rest.split_at(rest.find('/')?);
+    (Some(host) == request.header("Host")).then_some(page)
+}
+
+/// Reads `message`, `file`, `line` and `column` from the query or an urlencoded body.
+/// A request without a message clears the current error.
+fn parse_build_error(request: &Request) -> Option<BuildError> {
+    let mut error = BuildError::default();
+    for (key, value) in request.form() {
+        match key.as_str() {
+            "message" => error.message = value,
+            "file" => error.file = Some(value),
+            "line" => error.line = value.parse().ok(),
+            "column" => error.column = value.parse().ok(),
+            _ => {}
         }
-        response.push_str(&format!(
-            "<li><a href=\"{}\">{}</a></li>",
-            file_name, file_name
-        ));
     }
-
-    drop(entries);
-    if let Some(found) = found_index {
-        return Ok(serve_file(&found, stream, fs, head).await?);
+    match error.message.is_empty() {
+        true => None,
+        false => Some(error),
     }
+}
 
-    response.push_str("</ul></body></html>");
-    let _ = stream.write(response.as_bytes()).await?;
-    Ok(())
+async fn serve_directory(
+    request: &Request,
+    url: &str,
+    dir: &Path,
+    ctx: &Context<impl FileSystemInterface>,
+) -> crate::Result<Response> {
+    let mut view = View::from_query(&request.query_pairs());
+    view.json |= request
+        .header("Accept")
+        .is_some_and(|v| v.contains("application/json"));
+    let index = dir.join("index.html");
+    if !view.json && ctx.fs.kind(&index).await == Some(Kind::File) {
+        let url = format!("{}/index.html", url.trim_end_matches('/'));
+        return serve_file(&url, &index, ctx).await;
+    }
+    let mut entries = listing::read(&ctx.fs, dir).await?;
+    view.apply(&mut entries);
+    if view.json {
+        let body = listing::render_json(url, &entries, &view).into_bytes();
+        return Ok(Response::ok("application/json", body)
+            .with_header("Cache-Control", "no-cache")
+            .with_header("Vary", "Accept"));
+    }
+    let mut body = listing::render(url, &entries, &view, &ctx.options).into_bytes();
+    inject(&mut body, &ctx.options);
+    Ok(Response::ok("text/html; charset=utf-8", body)
+        .with_header("Cache-Control", "no-cache")
+        .with_header("Vary", "Accept"))
 }
 
 async fn serve_file(
+    url: &str,
     file_path: &Path,
-    stream: &mut TcpStream,
-    fs: impl FileSystemInterface,
-    head: bool,
-) -> crate::Result<()> {
-    let is_html = file_path
-        .as_os_str()
-        .to_str()
-        .unwrap_or_default()
-        .ends_with(".html");
+    ctx: &Context<impl FileSystemInterface>,
+) -> crate::Result<Response> {
     let mime = mime_guess::from_path(file_path).first_or_octet_stream();
 
-    let mut contents = fs.get_file(file_path).await?.read_to_end().await;
-    if is_html {
-        contents.append(
-            &mut format!("<script defer>{}</script>", include_str!("updater.js"))
-                .as_bytes()
-                .to_vec(),
-        )
+    let mut contents = ctx.fs.get_file(file_path).await?.read_to_end().await;
+    if is_html(&mime) {
+        inject(&mut contents, &ctx.options);
+    } else if ctx.options.hot && is_javascript(&mime) {
+        inject_hot(&mut contents, &ctx.options);
+    } else if mime == mime_guess::mime::TEXT_CSS {
+        ctx.clients
+            .record_imports(url, &String::from_utf8_lossy(&contents));
     }
 
-    let response = format!(
-        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
-        mime,
-        contents.len()
-    );
-    let _ = stream.write(response.as_bytes()).await;
-    if head {
-        return Ok(());
-    }
-    let _ = stream.write(&contents).await;
-    Ok(())
+    Ok(Response::ok(mime, contents).with_header("Cache-Control", "no-cache"))
 }
 
-async fn serve_404(stream: &mut TcpStream) {
-    let response = "HTTP/1.1 404 NOT FOUND\r\n\r\n";
-    let _ = stream.write(response.as_bytes()).await;
+fn serve_script(script: &str) -> Response {
+    Response::ok("text/javascript", script.as_bytes().to_vec())
+        .with_header("Cache-Control", "no-cache")
 }
 
-async fn serve_500(stream: &mut TcpStream) {
-    let response = "HTTP/1.1 500 INTERNAL SERVER ERROR\r\n\r\n";
-    let _ = stream.write(response.as_bytes()).await;
+fn serve_404() -> Response {
+    Response::new("404 NOT FOUND")
 }
 
-async fn serve_favicon(
-    path: &Path,
-    stream: &mut TcpStream,
-    fs: impl FileSystemInterface,
-    head: bool,
-) {
+fn serve_500() -> Response {
+    Response::new("500 INTERNAL SERVER ERROR")
+}
+
+const FAVICON: &[u8] = include_bytes!("../favicon.ico");
+
+/// Serves `/favicon.ico` from the served directory, or the built-in icon.
+async fn serve_favicon(path: &Path, fs: &impl FileSystemInterface) -> Response {
     let bytes = match fs.get_file(path).await {
         Ok(mut v) => v.read_to_end().await,
-        Err(_) => include_bytes!("../favicon.ico").to_vec(),
+        Err(_) => FAVICON.to_vec(),
     };
-    let response = format!(
-        "HTTP/1.1 200 OK\r\nContent-Type: image/x-icon\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
-        bytes.len()
-    );
-    let _ = stream.write(response.as_bytes()).await;
-    if head {
-        return;
-    }
-    let _ = stream.write(&bytes).await;
+    favicon_response(bytes)
+}
+
+fn favicon_response(bytes: Vec<u8>) -> Response {
+    Response::ok("image/x-icon", bytes)
 }
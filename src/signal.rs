@@ -1,10 +1,52 @@
-use std::path::PathBuf;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 use tokio::sync::broadcast::{channel, Receiver, Sender};
 
+use crate::json;
+
+/// A build error reported by an external tool, shown as an overlay in the browser.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildError {
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+impl BuildError {
+    pub(crate) fn to_json(&self) -> String {
+        format!(
+            "{{\"message\":{},\"file\":{},\"line\":{},\"column\":{}}}",
+            json::string(&self.message),
+            json::optional(self.file.as_deref().map(json::string)),
+            json::optional(self.line),
+            json::optional(self.column)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Event {
+    Changed(PathBuf),
+    /// Entries were added to or removed from the directory, relative to the served one.
+    DirChanged(PathBuf),
+    Error(BuildError),
+    ClearError,
+    /// Interaction of client `from` on `page`, mirrored to the other clients there.
+    Sync {
+        from: u64,
+        page: String,
+        data: String,
+    },
+}
+
 pub struct Signal {
-    tx: Sender<PathBuf>,
-    rx: Receiver<PathBuf>,
+    tx: Sender<Event>,
+    rx: Receiver<Event>,
+    error: Arc<Mutex<Option<BuildError>>>,
 }
 
 impl Default for Signal {
@@ -18,6 +60,7 @@ impl Clone for Signal {
         Self {
             rx: self.tx.subscribe(),
             tx: self.tx.clone(),
+            error: self.error.clone(),
         }
     }
 }
@@ -25,14 +68,48 @@ impl Clone for Signal {
 impl Signal {
     fn new() -> Self {
         let (tx, rx) = channel(100);
-        Signal { tx, rx }
+        Signal {
+            tx,
+            rx,
+            error: Arc::new(Mutex::new(None)),
+        }
     }
 
+    /// Notifies all clients that `file` changed. A pending build error is cleared.
     pub fn send_signal(&self, file: PathBuf) {
-        let _ = self.tx.send(file).unwrap();
+        self.error.lock().unwrap().take();
+        let _ = self.tx.send(Event::Changed(file));
+    }
+
+    /// Refreshes clients showing the listing of `dir`, after entries were added to or
+    /// removed from it. `dir` is relative to the served directory.
+    pub fn send_dir_changed(&self, dir: PathBuf) {
+        let _ = self.tx.send(Event::DirChanged(dir));
+    }
+
+    /// Shows `error` as an overlay on every connected client until the next change.
+    pub fn send_error(&self, error: BuildError) {
+        *self.error.lock().unwrap() = Some(error.clone());
+        let _ = self.tx.send(Event::Error(error));
+    }
+
+    /// Removes the error overlay without reloading.
+    pub fn clear_error(&self) {
+        if self.error.lock().unwrap().take().is_some() {
+            let _ = self.tx.send(Event::ClearError);
+        }
+    }
+
+    /// The error that is currently shown, if any.
+    pub fn current_error(&self) -> Option<BuildError> {
+        self.error.lock().unwrap().clone()
+    }
+
+    pub(crate) fn send_event(&self, event: Event) {
+        let _ = self.tx.send(event);
     }
 
-    pub(crate) async fn wait_signal(&self) -> PathBuf {
-        self.rx.resubscribe().recv().await.unwrap()
+    pub(crate) fn subscribe(&self) -> Receiver<Event> {
+        self.rx.resubscribe()
     }
 }
@@ -0,0 +1,65 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    io::{self, AsyncReadExt as _, AsyncWriteExt as _},
+    net::{tcp::OwnedWriteHalf, TcpStream},
+    sync::broadcast::error::RecvError,
+};
+
+use crate::{
+    clients::Client,
+    headers,
+    http::{Request, Response},
+    live, Context, FileSystemInterface,
+};
+
+/// Streams the live-reload messages as Server-Sent Events, for browsers and proxies
+/// that don't support WebSockets.
+pub async fn handle_events<T: FileSystemInterface + 'static>(
+    stream: TcpStream,
+    request: &Request,
+    ctx: Arc<Context<T>>,
+    client: Client,
+) -> io::Result<()> {
+    let (mut read_stream, mut write_stream) = stream.into_split();
+    let mut response = Response::new("200 OK")
+        .with_header("Content-Type", "text/event-stream")
+        .with_header("Cache-Control", "no-cache");
+    headers::apply(&ctx.options, request, &mut response);
+    response.write_head(&mut write_stream).await?;
+    let _registration = ctx.clients.register(client.clone());
+    let mut events = ctx.signal.subscribe();
+    for msg in live::handshake(&ctx, &client) {
+        send_event(&mut write_stream, &msg).await?;
+    }
+    let mut keepalive = tokio::time::interval(Duration::from_secs(15));
+    let mut closed = [0; 1];
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if let Some(msg) = live::message(&ctx, &client, event).await {
+                        send_event(&mut write_stream, &msg).await?;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            _ = keepalive.tick() => write_stream.write_all(b": keepalive\n\n").await?,
+            // the browser doesn't send anything, reading only notices a closed connection
+            _ = read_stream.read(&mut closed) => break,
+        }
+    }
+    Ok(())
+}
+
+async fn send_event(stream: &mut OwnedWriteHalf, message: &str) -> io::Result<()> {
+    let mut event = String::new();
+    for line in message.split('\n') {
+        event.push_str("data: ");
+        event.push_str(line);
+        event.push('\n');
+    }
+    event.push('\n');
+    stream.write_all(event.as_bytes()).await
+}
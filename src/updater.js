@@ -1,37 +1,545 @@
+// the server's endpoints live next to this script, e.g. `/__live/`
+const PREFIX = new URL(".", document.currentScript.src).pathname;
+
 function getAbsoluteUrl(url) {
   return new URL(url, window.location).href;
 }
 
-const socket = new WebSocket(`ws://${window.location.host}/ws`);
-socket.onmessage = (event) => {
-  if (event.data === "reload") {
-    window.location.reload();
-  } else if (event.data.startsWith("update-css://")) {
-    const filePath = event.data.substring(13);
+// adds or increments a `counter` query parameter so the browser fetches the file again
+function bustUrl(href) {
+  const url = new URL(href, window.location);
+  const counter = Number.parseInt(url.searchParams.get("counter") ?? "0") || 0;
+  url.searchParams.set("counter", counter + 1);
+  return url.toString();
+}
+
+function sameFile(href, filePath, base = window.location) {
+  try {
+    const url = new URL(href, base);
+    const file = new URL(filePath, window.location);
+    return url.origin === file.origin && url.pathname === file.pathname;
+  } catch {
+    return false;
+  }
+}
+
+function bustCssUrls(text, filePath, base) {
+  return text.replace(/url\(\s*(['"]?)(.*?)\1\s*\)/g, (match, quote, href) =>
+    sameFile(href, filePath, base) ? `url("${bustUrl(new URL(href, base))}")` : match,
+  );
+}
+
+// `@import "…"` and `@import url(…)` in <style> blocks
+function bustCssImports(text, filePath, base) {
+  const quoted = text.replace(/@import\s+(['"])(.*?)\1/gi, (match, quote, href) =>
+    sameFile(href, filePath, base) ? `@import "${bustUrl(new URL(href, base))}"` : match,
+  );
+  return bustCssUrls(quoted, filePath, base);
+}
+
+function bustSrcset(srcset, filePath) {
+  return srcset
+    .split(",")
+    .map((candidate) => {
+      const [href, ...descriptors] = candidate.trim().split(/\s+/);
+      return sameFile(href, filePath) ? [bustUrl(href), ...descriptors].join(" ") : candidate.trim();
+    })
+    .join(", ");
+}
+
+// swaps images, media and fonts in place instead of reloading the page
+function updateAsset(filePath) {
+  for (const element of document.querySelectorAll("img, source, video, audio, input[type=image]")) {
+    let changed = false;
+    for (const attribute of ["src", "poster"]) {
+      const value = element.getAttribute(attribute);
+      if (value && sameFile(value, filePath)) {
+        element.setAttribute(attribute, bustUrl(value));
+        changed = true;
+      }
+    }
+    const srcset = element.getAttribute("srcset");
+    if (srcset) {
+      const busted = bustSrcset(srcset, filePath);
+      changed ||= busted !== srcset;
+      element.setAttribute("srcset", busted);
+    }
+    if (changed && element.tagName === "SOURCE") {
+      element.parentElement?.load?.();
+    }
+  }
+  for (const element of document.querySelectorAll("[style]")) {
+    const style = element.getAttribute("style");
+    if (style.includes("url(")) {
+      element.setAttribute("style", bustCssUrls(style, filePath, window.location));
+    }
+  }
+  for (const sheet of document.styleSheets) {
+    try {
+      updateRules(sheet.cssRules, filePath, sheet.href ?? window.location);
+    } catch {
+      // cross-origin stylesheets can't be read
+    }
+  }
+}
+
+function updateRules(rules, filePath, base) {
+  for (const rule of rules) {
+    if (rule.cssRules) updateRules(rule.cssRules, filePath, base);
+    if (!rule.style) continue;
+    if (rule instanceof CSSFontFaceRule) {
+      const src = rule.style.getPropertyValue("src");
+      const busted = bustCssUrls(src, filePath, base);
+      if (busted === src) continue;
+      const family = rule.style.getPropertyValue("font-family").replace(/^["']|["']$/g, "");
+      const descriptors = {};
+      for (const name of ["style", "weight", "stretch", "display"]) {
+        const value = rule.style.getPropertyValue(`font-${name}`);
+        if (value) descriptors[name] = value;
+      }
+      const unicodeRange = rule.style.getPropertyValue("unicode-range");
+      if (unicodeRange) descriptors.unicodeRange = unicodeRange;
+      new FontFace(family, busted, descriptors)
+        .load()
+        .then((font) => document.fonts.add(font))
+        .catch(() => {});
+      continue;
+    }
+    for (const property of rule.style) {
+      const value = rule.style.getPropertyValue(property);
+      if (!value.includes("url(")) continue;
+      const busted = bustCssUrls(value, filePath, base);
+      if (busted !== value) {
+        rule.style.setProperty(property, busted, rule.style.getPropertyPriority(property));
+      }
+    }
+  }
+}
+
+const OVERLAY_ID = "__rusty_live_server_error";
+
+function showError(error) {
+  hideError();
+  const overlay = document.createElement("div");
+  overlay.id = OVERLAY_ID;
+  overlay.style.cssText =
+    "position:fixed;inset:0;z-index:2147483647;overflow:auto;padding:32px;" +
+    "background:rgba(24,24,24,0.92);color:#e8e8e8;font:14px/1.5 monospace;";
+  const close = document.createElement("button");
+  close.textContent = "×";
+  close.title = "Dismiss";
+  close.style.cssText =
+    "position:absolute;top:12px;right:16px;background:none;border:none;" +
+    "color:inherit;font-size:28px;cursor:pointer;";
+  close.onclick = hideError;
+  const title = document.createElement("div");
+  title.style.cssText = "color:#ff5555;font-size:18px;margin-bottom:16px;";
+  title.textContent = "Build failed";
+  overlay.append(close, title);
+  if (error.file) {
+    const location = document.createElement("div");
+    location.style.cssText = "color:#8be9fd;margin-bottom:16px;";
+    location.textContent = [error.file, error.line, error.column]
+      .filter((v) => v !== null && v !== undefined)
+      .join(":");
+    overlay.append(location);
+  }
+  const message = document.createElement("pre");
+  message.style.cssText = "margin:0;white-space:pre-wrap;";
+  message.textContent = error.message;
+  overlay.append(message);
+  document.body.append(overlay);
+}
+
+function hideError() {
+  document.getElementById(OVERLAY_ID)?.remove();
+}
+
+let config = null;
+const pendingConsole = [];
+
+function sendConsole(level, args) {
+  const text = args.map(formatConsoleArg).join(" ");
+  if (config === null) {
+    if (pendingConsole.length < 100) pendingConsole.push([level, text]);
+  } else if (config.console && canSend()) {
+    send(`console://${level}:${text}`);
+  }
+}
+
+function formatConsoleArg(arg) {
+  if (typeof arg === "string") return arg;
+  if (arg instanceof Error) return arg.stack || `${arg.name}: ${arg.message}`;
+  try {
+    return JSON.stringify(arg) ?? String(arg);
+  } catch {
+    return String(arg);
+  }
+}
+
+// wrapped until the handshake tells whether the output is forwarded, since wrapped
+// functions show up as the call site in devtools
+const originalConsole = {};
+for (const level of ["log", "info", "warn", "error", "debug"]) {
+  const original = console[level];
+  originalConsole[level] = original;
+  console[level] = (...args) => {
+    original.apply(console, args);
+    sendConsole(level, args);
+  };
+}
+
+function restoreConsole() {
+  Object.assign(console, originalConsole);
+}
+
+window.addEventListener("error", (event) => {
+  sendConsole("error", [
+    event.error ?? `${event.message} (${event.filename}:${event.lineno}:${event.colno})`,
+  ]);
+});
+
+window.addEventListener("unhandledrejection", (event) => {
+  sendConsole("error", ["Unhandled rejection:", event.reason]);
+});
+
+function cssPath(element) {
+  const parts = [];
+  while (element && element !== document.body && element.nodeType === 1) {
+    if (element.id) {
+      parts.unshift(`#${CSS.escape(element.id)}`);
+      break;
+    }
+    const tag = element.tagName.toLowerCase();
+    const index = [...element.parentNode.children]
+      .filter((v) => v.tagName === element.tagName)
+      .indexOf(element);
+    parts.unshift(`${tag}:nth-of-type(${index + 1})`);
+    element = element.parentElement;
+  }
+  return parts.length ? parts.join(" > ") : "body";
+}
+
+// events caused by applying a remote interaction must not be sent back
+let syncMutedUntil = 0;
+
+function sendSync(data) {
+  if (!config?.sync || Date.now() < syncMutedUntil) return;
+  if (canSend()) {
+    send(`sync://${JSON.stringify(data)}`);
+  }
+}
+
+let scrollTimer = null;
+window.addEventListener("scroll", () => {
+  if (scrollTimer) return;
+  scrollTimer = setTimeout(() => {
+    scrollTimer = null;
+    const root = document.documentElement;
+    sendSync({
+      type: "scroll",
+      x: window.scrollX / Math.max(1, root.scrollWidth - window.innerWidth),
+      y: window.scrollY / Math.max(1, root.scrollHeight - window.innerHeight),
+    });
+  }, 50);
+});
+
+document.addEventListener(
+  "click",
+  (event) => {
+    const link = event.target.closest?.("a[href]");
+    if (link && link.origin === window.location.origin && !link.target) {
+      sendSync({ type: "navigate", url: link.pathname + link.search + link.hash });
+    } else if (!link) {
+      sendSync({ type: "click", selector: cssPath(event.target) });
+    }
+  },
+  true,
+);
+
+document.addEventListener(
+  "input",
+  (event) => {
+    const target = event.target;
+    if (!("value" in target) || target.type === "password") return;
+    sendSync({
+      type: "input",
+      selector: cssPath(target),
+      value: target.value,
+      checked: target.checked,
+    });
+  },
+  true,
+);
+
+function applySync(data) {
+  syncMutedUntil = Date.now() + 100;
+  if (data.type === "scroll") {
+    const root = document.documentElement;
+    window.scrollTo(
+      data.x * (root.scrollWidth - window.innerWidth),
+      data.y * (root.scrollHeight - window.innerHeight),
+    );
+  } else if (data.type === "navigate") {
+    // other clients must not send this page to another site or run `javascript:` URLs
+    const url = new URL(data.url, window.location.href);
+    if (url.origin === window.location.origin) window.location.href = url.href;
+  } else if (data.type === "click") {
+    document.querySelector(data.selector)?.click();
+  } else if (data.type === "input") {
+    const target = document.querySelector(data.selector);
+    if (!target) return;
+    target.value = data.value;
+    if (typeof data.checked === "boolean") target.checked = data.checked;
+    target.dispatchEvent(new Event("input", { bubbles: true }));
+    target.dispatchEvent(new Event("change", { bubbles: true }));
+  }
+}
+
+const STATE_KEY = `__rusty_live_server_state:${window.location.pathname}`;
+
+function reload() {
+  const state = {};
+  if (config?.preserveScroll) {
+    state.scroll = { window: [window.scrollX, window.scrollY], elements: {} };
+    for (const element of document.querySelectorAll("[id]")) {
+      if (element.scrollTop || element.scrollLeft) {
+        state.scroll.elements[element.id] = [element.scrollLeft, element.scrollTop];
+      }
+    }
+  }
+  if (config?.preserveForms) {
+    state.forms = {};
+    for (const field of document.querySelectorAll("input, textarea, select")) {
+      if (field.type === "password" || field.type === "file") continue;
+      state.forms[cssPath(field)] = [field.value, field.checked];
+    }
+  }
+  try {
+    sessionStorage.setItem(STATE_KEY, JSON.stringify(state));
+  } catch {}
+  window.location.reload();
+}
+
+function restoreState() {
+  let state;
+  try {
+    state = JSON.parse(sessionStorage.getItem(STATE_KEY));
+    sessionStorage.removeItem(STATE_KEY);
+  } catch {}
+  if (!state) return;
+  for (const [selector, [value, checked]] of Object.entries(state.forms ?? {})) {
+    const field = document.querySelector(selector);
+    if (!field) continue;
+    if (field.type === "checkbox" || field.type === "radio") {
+      field.checked = checked;
+    } else {
+      field.value = value;
+    }
+  }
+  if (state.scroll) {
+    for (const [id, [left, top]] of Object.entries(state.scroll.elements)) {
+      document.getElementById(id)?.scrollTo(left, top);
+    }
+    window.scrollTo(...state.scroll.window);
+  }
+}
+
+if (document.readyState === "complete") {
+  restoreState();
+} else {
+  window.addEventListener("load", restoreState);
+}
+
+// <head> of the last version of the page as served, see `morphPage`
+let servedHead = null;
+
+function parseHtml(html) {
+  return new DOMParser().parseFromString(html, "text/html");
+}
+
+function loadServedHead() {
+  fetch(window.location.href, { cache: "no-store" })
+    .then((response) => response.text())
+    .then((html) => {
+      servedHead ??= parseHtml(html).head.innerHTML;
+    })
+    .catch(() => {});
+}
+
+function morphPage(html) {
+  const next = parseHtml(html);
+  if (servedHead === null || next.head.innerHTML !== servedHead) {
+    reload();
+    return;
+  }
+  hideError();
+  morphElement(document.body, next.body);
+}
+
+function isSameNode(from, to) {
+  if (from.nodeType !== to.nodeType || from.nodeName !== to.nodeName) return false;
+  return from.nodeType !== Node.ELEMENT_NODE || (from.id || "") === (to.id || "");
+}
+
+function morphElement(from, to) {
+  if (from.tagName === "SCRIPT") return;
+  const focused = from === document.activeElement;
+  for (const { name, value } of to.attributes) {
+    if (focused && (name === "value" || name === "checked")) continue;
+    if (from.getAttribute(name) !== value) from.setAttribute(name, value);
+  }
+  for (const { name } of [...from.attributes]) {
+    if (!to.hasAttribute(name)) from.removeAttribute(name);
+  }
+  if (from.tagName === "TEXTAREA" && !focused) from.value = to.value;
+  let cursor = from.firstChild;
+  for (const next of [...to.childNodes]) {
+    let match = null;
+    for (let candidate = cursor, i = 0; candidate && i < 10; candidate = candidate.nextSibling, i++) {
+      if (isSameNode(candidate, next)) {
+        match = candidate;
+        break;
+      }
+    }
+    if (match === null) {
+      from.insertBefore(document.importNode(next, true), cursor);
+      continue;
+    }
+    if (match !== cursor) from.insertBefore(match, cursor);
+    cursor = match.nextSibling;
+    if (match.nodeType === Node.ELEMENT_NODE) {
+      morphElement(match, next);
+    } else if (match.nodeValue !== next.nodeValue) {
+      match.nodeValue = next.nodeValue;
+    }
+  }
+  while (cursor) {
+    const next = cursor.nextSibling;
+    if (cursor.id !== OVERLAY_ID && cursor.tagName !== "SCRIPT") cursor.remove();
+    cursor = next;
+  }
+}
+
+// re-imports a changed ES module that accepted hot updates, see hot.js
+async function hotUpdate(filePath) {
+  const modules = globalThis.__rustyLiveServerModules;
+  const path = new URL(filePath, window.location).pathname;
+  const record = modules?.get(path);
+  if (!record?.accepted) {
+    reload();
+    return;
+  }
+  hideError();
+  const data = {};
+  for (const callback of record.disposeCallbacks) callback(data);
+  record.nextData = data;
+  try {
+    const module = await import(bustUrl(path));
+    for (const callback of record.acceptCallbacks) callback(module);
+  } catch (error) {
+    console.error(error);
+    reload();
+  }
+}
+
+// lets the server reload only the pages that use a changed file
+function sendResources(entries) {
+  const paths = entries
+    .map((entry) => new URL(entry.name, window.location))
+    .filter((url) => url.origin === window.location.origin)
+    .map((url) => url.pathname);
+  if (paths.length && canSend()) {
+    send(`resources://${paths.join("\n")}`);
+  }
+}
+
+let socket = null;
+let eventSource = null;
+let resourceObserver = null;
+
+function canSend() {
+  return socket?.readyState === WebSocket.OPEN || (eventSource !== null && config !== null);
+}
+
+function send(text) {
+  if (socket?.readyState === WebSocket.OPEN) {
+    socket.send(text);
+  } else if (eventSource !== null && config !== null) {
+    fetch(`${PREFIX}message?client=${config.id}`, { method: "POST", body: text, keepalive: true }).catch(
+      () => {},
+    );
+  }
+}
+
+function handleMessage(data) {
+  if (data.startsWith("config://")) {
+    config = JSON.parse(data.substring(9));
+    for (const [level, text] of pendingConsole.splice(0)) {
+      if (config.console) send(`console://${level}:${text}`);
+    }
+    if (!config.console) restoreConsole();
+    if (config.morph) loadServedHead();
+    sendResources(performance.getEntriesByType("resource"));
+    resourceObserver ??= new PerformanceObserver((list) => sendResources(list.getEntries()));
+    resourceObserver.observe({ type: "resource" });
+  } else if (data.startsWith("sync://")) {
+    applySync(JSON.parse(data.substring(7)));
+  } else if (data.startsWith("morph://")) {
+    morphPage(data.substring(data.indexOf("\n") + 1));
+  } else if (data.startsWith("hot://")) {
+    hotUpdate(data.substring(6));
+  } else if (data === "reload") {
+    reload();
+  } else if (data === "clear-error") {
+    hideError();
+  } else if (data.startsWith("error://")) {
+    showError(JSON.parse(data.substring(8)));
+  } else if (data.startsWith("update-css://")) {
+    hideError();
+    // the changed file followed by the stylesheets that @import it
+    const filePaths = data.substring(13).split("\n");
     const links = document.getElementsByTagName("link");
     for (const link of links) {
       if (link.rel !== "stylesheet") continue;
       const clonedLink = link.cloneNode(true);
-      if (getAbsoluteUrl(link.href).startsWith(getAbsoluteUrl(filePath))) {
-        const indexOf = link.href.indexOf("?");
-        if (indexOf !== -1 && link.href.slice(indexOf).includes("counter=")) {
-          const url = new URL(link.href);
-          const params = new URLSearchParams(url.search);
-          const counter = params.get("counter");
-          if (counter) {
-            params.set("counter", Number.parseInt(counter) + 1);
-          }
-          url.search = params.toString();
-          clonedLink.href = url.toString();
-        } else {
-          clonedLink.href += `${indexOf !== -1 ? "&" : "?"}counter=1`;
-        }
+      if (filePaths.some((filePath) => getAbsoluteUrl(link.href).startsWith(getAbsoluteUrl(filePath)))) {
+        clonedLink.href = bustUrl(link.href);
       }
       link.replaceWith(clonedLink);
     }
+    for (const style of document.getElementsByTagName("style")) {
+      const css = filePaths.reduce(
+        (css, filePath) => bustCssImports(css, filePath, window.location),
+        style.textContent,
+      );
+      if (css !== style.textContent) style.textContent = css;
+    }
+  } else if (data.startsWith("update-asset://")) {
+    hideError();
+    updateAsset(data.substring(15));
   }
-};
+}
+
+// WebSocket with a fallback to Server-Sent Events if the upgrade is blocked
+function connect() {
+  const query = `page=${encodeURIComponent(window.location.pathname)}`;
+  let opened = false;
+  const protocol = window.location.protocol === "https:" ? "wss" : "ws";
+  socket = new WebSocket(`${protocol}://${window.location.host}${PREFIX}ws?${query}`);
+  socket.onopen = () => {
+    opened = true;
+  };
+  socket.onmessage = (event) => handleMessage(event.data);
+  socket.onerror = (error) => {
+    if (opened) {
+      console.error("Reload WebSocket error:", error);
+      return;
+    }
+    socket = null;
+    eventSource = new EventSource(`${PREFIX}events?${query}`);
+    eventSource.onmessage = (event) => handleMessage(event.data);
+  };
+}
 
-socket.onerror = (error) => {
-  console.error("Reload WebSocket error:", error);
-};
+connect();
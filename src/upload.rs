@@ -0,0 +1,142 @@
+//! Changes to the served files when [`crate::Options::writable`] is set. The watcher
+//! picks them up like any other change.
+
+use std::path::Path;
+
+use crate::{
+    http::{parse_form, parse_multipart, Request, Response},
+    Context, FileSystemInterface, Kind,
+};
+
+/// Handles `PUT`, `DELETE`, `MKCOL` and `POST` for the file or directory `file_path`.
+pub async fn handle(
+    request: &Request,
+    file_path: &Path,
+    ctx: &Context<impl FileSystemInterface>,
+) -> Response {
+    if request.body_too_large() {
+        return Response::new("413 Payload Too Large");
+    }
+    match request.method.as_str() {
+        "PUT" => put(request, file_path, ctx).await,
+        "DELETE" => delete(file_path, ctx).await,
+        "MKCOL" => mkcol(request, file_path, ctx).await,
+        _ => post(request, file_path, ctx).await,
+    }
+}
+
+/// Creates or replaces a file with the request body.
+async fn put(
+    request: &Request,
+    file_path: &Path,
+    ctx: &Context<impl FileSystemInterface>,
+) -> Response {
+    let existed = match ctx.fs.kind(file_path).await {
+        Some(Kind::Dir) => return Response::new("409 Conflict"),
+        Some(Kind::File) => true,
+        None => false,
+    };
+    if request.path.ends_with('/') || !parent_is_dir(file_path, ctx).await {
+        return Response::new("409 Conflict");
+    }
+    match ctx.fs.write_file(file_path, request.body.clone()).await {
+        Ok(()) if existed => Response::new("204 No Content"),
+        Ok(()) => Response::new("201 Created"),
+        Err(_) => Response::new("500 INTERNAL SERVER ERROR"),
+    }
+}
+
+async fn delete(file_path: &Path, ctx: &Context<impl FileSystemInterface>) -> Response {
+    if file_path == ctx.base_dir {
+        return Response::new("403 Forbidden");
+    }
+    if !ctx.fs.exists(file_path).await {
+        return Response::new("404 NOT FOUND");
+    }
+    match ctx.fs.remove(file_path).await {
+        Ok(()) => Response::new("204 No Content"),
+        Err(_) => Response::new("500 INTERNAL SERVER ERROR"),
+    }
+}
+
+/// Creates a directory, like WebDAV's `MKCOL`.
+async fn mkcol(
+    request: &Request,
+    file_path: &Path,
+    ctx: &Context<impl FileSystemInterface>,
+) -> Response {
+    if ctx.fs.exists(file_path).await {
+        return Response::new("405 Method Not Allowed");
+    }
+    if !parent_is_dir(file_path, ctx).await {
+        return Response::new("409 Conflict");
+    }
+    if !request.body.is_empty() {
+        return Response::new("415 Unsupported Media Type");
+    }
+    match ctx.fs.create_dir(file_path).await {
+        Ok(()) => Response::new("201 Created"),
+        Err(_) => Response::new("500 INTERNAL SERVER ERROR"),
+    }
+}
+
+/// Saves the files of a `multipart/form-data` upload into the directory, or creates
+/// the folder named by a `mkdir` field. Redirects back to the listing.
+async fn post(request: &Request, dir: &Path, ctx: &Context<impl FileSystemInterface>) -> Response {
+    if ctx.fs.kind(dir).await != Some(Kind::Dir) {
+        return Response::new("405 Method Not Allowed");
+    }
+    let content_type = request.header("Content-Type").unwrap_or_default();
+    // uploaded files come with a name, folders to create without
+    let fields: Vec<(Option<String>, Vec<u8>)> =
+        match content_type.starts_with("multipart/form-data") {
+            true => parse_multipart(content_type, &request.body)
+                .into_iter()
+                .filter(|part| {
+                    part.filename
+                        .as_deref()
+                        .map_or(part.name == "mkdir", |v| !v.is_empty())
+                })
+                .map(|part| (part.filename, part.data))
+                .collect(),
+            false => parse_form(&String::from_utf8_lossy(&request.body))
+                .into_iter()
+                .filter(|(key, _)| key == "mkdir")
+                .map(|(_, value)| (None, value.into_bytes()))
+                .collect(),
+        };
+    let mut changed = false;
+    for (filename, data) in fields {
+        let result = match filename {
+            Some(filename) => match file_name(&filename) {
+                Some(name) => ctx.fs.write_file(&dir.join(name), data).await,
+                None => continue,
+            },
+            None => match file_name(&String::from_utf8_lossy(&data)) {
+                Some(name) => ctx.fs.create_dir(&dir.join(name)).await,
+                None => continue,
+            },
+        };
+        if result.is_err() {
+            return Response::new("500 INTERNAL SERVER ERROR");
+        }
+        changed = true;
+    }
+    match changed {
+        true => Response::new("303 See Other").with_header("Location", &request.path),
+        false => Response::new("400 Bad Request"),
+    }
+}
+
+async fn parent_is_dir(path: &Path, ctx: &Context<impl FileSystemInterface>) -> bool {
+    match path.parent() {
+        Some(parent) => ctx.fs.kind(parent).await == Some(Kind::Dir),
+        None => false,
+    }
+}
+
+/// The last segment of an uploaded name, so it can't leave the directory.
+fn file_name(name: &str) -> Option<&str> {
+    let name = name.rsplit(['/', '\\']).next()?.trim();
+    (!matches!(name, "" | "." | "..")).then_some(name)
+}
@@ -0,0 +1,799 @@
+//! WebDAV class 1 and 2 below `{prefix}/dav/`, so editors get the files without the
+//! reload script. Locks and dead properties are kept in memory.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    fs::{File as _, Kind, Metadata},
+    http::{percent_decode, percent_encode, Request, Response},
+    listing::{self, escape},
+    routing::local_path,
+    upload,
+    xml::{self, Element},
+    Context, Dir as _, FileSystemInterface,
+};
+
+const DAV: &str = "DAV:";
+/// Lock timeout in seconds without a `Timeout` header, and the longest one granted.
+const DEFAULT_TIMEOUT: u64 = 3600;
+const MAX_TIMEOUT: u64 = 24 * 3600;
+
+static NEXT_LOCK: AtomicU64 = AtomicU64::new(0);
+
+/// Locks and dead properties, keyed by the decoded path relative to the served
+/// directory without leading or trailing `/`.
+#[derive(Debug, Default)]
+pub struct Dav {
+    locks: Mutex<Vec<Lock>>,
+    properties: Mutex<HashMap<String, Vec<Property>>>,
+}
+
+#[derive(Debug, Clone)]
+struct Lock {
+    token: String,
+    key: String,
+    infinite: bool,
+    exclusive: bool,
+    owner: String,
+    timeout: u64,
+    expires: Instant,
+}
+
+#[derive(Debug, Clone)]
+struct Property {
+    ns: String,
+    name: String,
+    value: String,
+}
+
+/// Whether the internal `endpoint` belongs to WebDAV.
+pub fn is_dav(endpoint: &str) -> bool {
+    endpoint == "dav" || endpoint.starts_with("dav/")
+}
+
+pub async fn handle(
+    request: &Request,
+    endpoint: &str,
+    ctx: &Context<impl FileSystemInterface>,
+) -> Response {
+    let raw = endpoint.trim_start_matches("dav");
+    let key = normalize(raw);
+    let file_path = local_path(ctx, raw);
+    ctx.dav.expire();
+    match request.method.as_str() {
+        "OPTIONS" => Response::new("200 OK")
+            .with_header("DAV", "1, 2")
+            .with_header("MS-Author-Via", "DAV")
+            .with_header(
+                "Allow",
+                "OPTIONS, GET, HEAD, PUT, DELETE, MKCOL, PROPFIND, PROPPATCH, COPY, MOVE, LOCK, UNLOCK",
+            ),
+        "GET" | "HEAD" => get(&file_path, ctx).await,
+        "PUT" | "MKCOL" => match ctx.dav.check(request, &key, false) {
+            Some(locked) => locked,
+            None => upload::handle(request, &file_path, ctx).await,
+        },
+        "DELETE" => match ctx.dav.check(request, &key, true) {
+            Some(locked) => locked,
+            None => {
+                let response = upload::handle(request, &file_path, ctx).await;
+                if response.status.starts_with('2') {
+                    ctx.dav.forget(&key);
+                }
+                response
+            }
+        },
+        "PROPFIND" => propfind(request, &key, &file_path, ctx).await,
+        "PROPPATCH" => match ctx.dav.check(request, &key, false) {
+            Some(locked) => locked,
+            None => proppatch(request, &key, &file_path, ctx).await,
+        },
+        "COPY" => copy(request, &key, &file_path, false, ctx).await,
+        "MOVE" => copy(request, &key, &file_path, true, ctx).await,
+        "LOCK" => lock(request, &key, &file_path, ctx).await,
+        "UNLOCK" => unlock(request, &key, ctx),
+        _ => Response::new("405 Method Not Allowed"),
+    }
+}
+
+/// The files as they are, without the reload script.
+async fn get(file_path: &Path, ctx: &Context<impl FileSystemInterface>) -> Response {
+    let Ok(metadata) = ctx.fs.metadata(file_path).await else {
+        return Response::new("404 NOT FOUND");
+    };
+    let response = match metadata.kind {
+        Kind::Dir => {
+            let Ok(entries) = listing::read(&ctx.fs, file_path).await else {
+                return Response::new("500 INTERNAL SERVER ERROR");
+            };
+            let mut names: Vec<_> = entries.into_iter().map(|v| v.name + "\n").collect();
+            names.sort();
+            Response::ok("text/plain; charset=utf-8", names.concat().into_bytes())
+        }
+        Kind::File => {
+            let Ok(mut file) = ctx.fs.get_file(file_path).await else {
+                return Response::new("500 INTERNAL SERVER ERROR");
+            };
+            let mime = mime_guess::from_path(file_path).first_or_octet_stream();
+            Response::ok(mime, file.read_to_end().await)
+        }
+    };
+    response.with_header("ETag", etag(&metadata)).with_header(
+        "Last-Modified",
+        http_date(metadata.modified.unwrap_or(UNIX_EPOCH)),
+    )
+}
+
+enum Find {
+    All,
+    Names,
+    Props(Vec<(String, String)>),
+}
+
+async fn propfind(
+    request: &Request,
+    key: &str,
+    file_path: &Path,
+    ctx: &Context<impl FileSystemInterface>,
+) -> Response {
+    let depth = request.header("Depth").unwrap_or("infinity");
+    if depth.eq_ignore_ascii_case("infinity") {
+        let body = format!("{XML}<D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>");
+        let mut response = Response::ok("application/xml; charset=utf-8", body.into_bytes());
+        response.status = "403 Forbidden";
+        return response;
+    }
+    let find = match body(request) {
+        Ok(None) => Find::All,
+        Ok(Some(root)) if root.is(DAV, "propfind") => {
+            if root.child(DAV, "propname").is_some() {
+                Find::Names
+            } else if let Some(prop) = root.child(DAV, "prop") {
+                Find::Props(
+                    prop.children
+                        .iter()
+                        .map(|v| (v.ns.clone(), v.name.clone()))
+                        .collect(),
+                )
+            } else {
+                Find::All
+            }
+        }
+        _ => return Response::new("400 Bad Request"),
+    };
+    let Ok(metadata) = ctx.fs.metadata(file_path).await else {
+        return Response::new("404 NOT FOUND");
+    };
+    let mut resources = vec![(key.to_string(), metadata)];
+    if depth == "1" && metadata.kind == Kind::Dir {
+        let Ok(mut entries) = ctx.fs.get_dir(file_path).await else {
+            return Response::new("500 INTERNAL SERVER ERROR");
+        };
+        while let Ok(Some(path)) = entries.get_next().await {
+            let (Some(name), Ok(metadata)) = (path.file_name(), ctx.fs.metadata(&path).await)
+            else {
+                continue;
+            };
+            let name = name.to_string_lossy();
+            let child = match key.is_empty() {
+                true => name.into_owned(),
+                false => format!("{key}/{name}"),
+            };
+            resources.push((child, metadata));
+        }
+    }
+
+    let mut body = format!("{XML}<D:multistatus xmlns:D=\"DAV:\">");
+    for (key, metadata) in resources {
+        let live = ctx.dav.live_properties(ctx, &key, &metadata);
+        let dead = ctx.dav.dead_properties(&key);
+        body.push_str(&format!(
+            "<D:response><D:href>{}</D:href>",
+            href(ctx, &key, &metadata)
+        ));
+        match &find {
+            Find::All => {
+                let mut found: Vec<_> = live
+                    .iter()
+                    .map(|(name, value)| format!("<D:{name}>{value}</D:{name}>"))
+                    .collect();
+                found.extend(dead.iter().map(dead_element));
+                body.push_str(&propstat(&found.concat(), "200 OK"));
+            }
+            Find::Names => {
+                let mut names: Vec<_> = live
+                    .iter()
+                    .map(|(name, _)| format!("<D:{name}/>"))
+                    .collect();
+                names.extend(dead.iter().map(|v| empty_element(&v.ns, &v.name)));
+                body.push_str(&propstat(&names.concat(), "200 OK"));
+            }
+            Find::Props(wanted) => {
+                let (mut found, mut missing) = (String::new(), String::new());
+                for (ns, name) in wanted {
+                    let live = live.iter().find(|(n, _)| ns == DAV && n == name);
+                    let dead = dead.iter().find(|v| &v.ns == ns && &v.name == name);
+                    match (live, dead) {
+                        (Some((name, value)), _) => {
+                            found.push_str(&format!("<D:{name}>{value}</D:{name}>"))
+                        }
+                        (None, Some(property)) => found.push_str(&dead_element(property)),
+                        (None, None) => missing.push_str(&empty_element(ns, name)),
+                    }
+                }
+                if !found.is_empty() {
+                    body.push_str(&propstat(&found, "200 OK"));
+                }
+                if !missing.is_empty() {
+                    body.push_str(&propstat(&missing, "404 Not Found"));
+                }
+            }
+        }
+        body.push_str("</D:response>");
+    }
+    body.push_str("</D:multistatus>");
+    multistatus(body)
+}
+
+/// Sets and removes dead properties. Live properties in the `DAV:` namespace are
+/// protected, which fails the whole request.
+async fn proppatch(
+    request: &Request,
+    key: &str,
+    file_path: &Path,
+    ctx: &Context<impl FileSystemInterface>,
+) -> Response {
+    let Ok(metadata) = ctx.fs.metadata(file_path).await else {
+        return Response::new("404 NOT FOUND");
+    };
+    let Ok(Some(root)) = body(request) else {
+        return Response::new("400 Bad Request");
+    };
+    if !root.is(DAV, "propertyupdate") {
+        return Response::new("400 Bad Request");
+    }
+    let mut updates = vec![];
+    for update in &root.children {
+        let set = update.is(DAV, "set");
+        if !set && !update.is(DAV, "remove") {
+            continue;
+        }
+        for prop in update.children.iter().filter(|v| v.is(DAV, "prop")) {
+            for property in &prop.children {
+                let property = Property {
+                    ns: property.ns.clone(),
+                    name: property.name.clone(),
+                    value: property.text.clone(),
+                };
+                updates.push((set, property));
+            }
+        }
+    }
+    let protected = updates.iter().any(|(_, v)| v.ns == DAV);
+    let mut statuses: Vec<(&str, String)> = vec![];
+    for (_, property) in &updates {
+        let status = match (protected, property.ns == DAV) {
+            (true, true) => "403 Forbidden",
+            (true, false) => "424 Failed Dependency",
+            (false, _) => "200 OK",
+        };
+        statuses.push((status, empty_element(&property.ns, &property.name)));
+    }
+    if !protected {
+        let mut properties = ctx.dav.properties.lock().unwrap();
+        let stored = properties.entry(key.to_string()).or_default();
+        for (set, property) in updates {
+            stored.retain(|v| v.ns != property.ns || v.name != property.name);
+            if set {
+                stored.push(property);
+            }
+        }
+    }
+    let mut body = format!(
+        "{XML}<D:multistatus xmlns:D=\"DAV:\"><D:response><D:href>{}</D:href>",
+        href(ctx, key, &metadata)
+    );
+    for status in ["200 OK", "403 Forbidden", "424 Failed Dependency"] {
+        let props: String = statuses
+            .iter()
+            .filter(|(v, _)| *v == status)
+            .map(|(_, v)| v.as_str())
+            .collect();
+        if !props.is_empty() {
+            body.push_str(&propstat(&props, status));
+        }
+    }
+    body.push_str("</D:response></D:multistatus>");
+    multistatus(body)
+}
+
+/// `COPY`, or `MOVE` if `remove` is set, to the `Destination` header.
+async fn copy(
+    request: &Request,
+    key: &str,
+    file_path: &Path,
+    remove: bool,
+    ctx: &Context<impl FileSystemInterface>,
+) -> Response {
+    let Some(destination) = request.header("Destination") else {
+        return Response::new("400 Bad Request");
+    };
+    // strip the scheme and host of an absolute URL
+    let destination = match destination.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|i| &rest[i..]).unwrap_or("/"),
+        None => destination,
+    };
+    let Some(raw) = ctx
+        .options
+        .internal_endpoint(destination)
+        .filter(|v| is_dav(v))
+        .map(|v| v.trim_start_matches("dav"))
+    else {
+        return Response::new("502 Bad Gateway");
+    };
+    let target_key = normalize(raw);
+    let target = local_path(ctx, raw);
+    // replacing an ancestor, or the root, would remove the source with it
+    if target_key == key
+        || target_key.is_empty()
+        || is_below(&target_key, key)
+        || is_below(key, &target_key)
+    {
+        return Response::new("403 Forbidden");
+    }
+    let Ok(metadata) = ctx.fs.metadata(file_path).await else {
+        return Response::new("404 NOT FOUND");
+    };
+    let locked = match remove {
+        true => ctx.dav.check(request, key, true),
+        false => None,
+    };
+    if let Some(locked) = locked.or_else(|| ctx.dav.check(request, &target_key, true)) {
+        return locked;
+    }
+    let overwrite = request.header("Overwrite") != Some("F");
+    let existed = ctx.fs.exists(&target).await;
+    if existed && !overwrite {
+        return Response::new("412 Precondition Failed");
+    }
+    let parent = target.parent().unwrap_or(&target);
+    if ctx.fs.kind(parent).await != Some(Kind::Dir) {
+        return Response::new("409 Conflict");
+    }
+    if existed && ctx.fs.remove(&target).await.is_err() {
+        return Response::new("500 INTERNAL SERVER ERROR");
+    }
+    let result = match remove {
+        true => ctx.fs.rename(file_path, &target).await,
+        false => {
+            let infinite = request.header("Depth") != Some("0");
+            copy_tree(&ctx.fs, file_path, &target, metadata, infinite).await
+        }
+    };
+    if result.is_err() {
+        return Response::new("500 INTERNAL SERVER ERROR");
+    }
+    ctx.dav.forget(&target_key);
+    if remove {
+        ctx.dav.moved(key, &target_key);
+    }
+    match existed {
+        true => Response::new("204 No Content"),
+        false => Response::new("201 Created"),
+    }
+}
+
+async fn copy_tree(
+    fs: &impl FileSystemInterface,
+    from: &Path,
+    to: &Path,
+    metadata: Metadata,
+    infinite: bool,
+) -> crate::Result<()> {
+    let mut pending = vec![(from.to_path_buf(), to.to_path_buf(), metadata)];
+    while let Some((from, to, metadata)) = pending.pop() {
+        match metadata.kind {
+            Kind::File => {
+                let contents = fs.get_file(&from).await?.read_to_end().await;
+                fs.write_file(&to, contents).await?;
+            }
+            Kind::Dir => {
+                fs.create_dir(&to).await?;
+                if !infinite && from != to {
+                    continue;
+                }
+                let mut entries = fs.get_dir(&from).await?;
+                while let Some(path) = entries.get_next().await? {
+                    let Some(name) = path.file_name() else {
+                        continue;
+                    };
+                    let metadata = fs.metadata(&path).await?;
+                    pending.push((path.clone(), to.join(name), metadata));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn lock(
+    request: &Request,
+    key: &str,
+    file_path: &Path,
+    ctx: &Context<impl FileSystemInterface>,
+) -> Response {
+    let timeout = request
+        .header("Timeout")
+        .and_then(|v| v.split(',').next())
+        .map(|v| match v.trim().strip_prefix("Second-") {
+            Some(seconds) => seconds.parse().unwrap_or(DEFAULT_TIMEOUT),
+            None => MAX_TIMEOUT,
+        })
+        .unwrap_or(DEFAULT_TIMEOUT)
+        .min(MAX_TIMEOUT);
+    let root = match body(request) {
+        Ok(v) => v,
+        Err(_) => return Response::new("400 Bad Request"),
+    };
+    let Some(info) = root.filter(|v| v.is(DAV, "lockinfo")) else {
+        // without a body, a lock is refreshed
+        let tokens = submitted_tokens(request);
+        let mut locks = ctx.dav.locks.lock().unwrap();
+        let Some(lock) = locks
+            .iter_mut()
+            .find(|v| covers(v, key) && tokens.contains(&v.token))
+        else {
+            return Response::new("412 Precondition Failed");
+        };
+        lock.timeout = timeout;
+        lock.expires = Instant::now() + Duration::from_secs(timeout);
+        let body = lock_body(ctx, lock);
+        return Response::ok("application/xml; charset=utf-8", body.into_bytes());
+    };
+    let exclusive = info
+        .child(DAV, "lockscope")
+        .is_some_and(|v| v.child(DAV, "exclusive").is_some());
+    let owner = match info.child(DAV, "owner") {
+        Some(owner) => match owner.child(DAV, "href") {
+            Some(href) => format!("<D:href>{}</D:href>", escape(href.text.trim())),
+            None => escape(owner.text.trim()),
+        },
+        None => String::new(),
+    };
+    let infinite = request.header("Depth") != Some("0");
+    let created = !ctx.fs.exists(file_path).await;
+    if created {
+        let parent = file_path.parent().unwrap_or(file_path);
+        if ctx.fs.kind(parent).await != Some(Kind::Dir) {
+            return Response::new("409 Conflict");
+        }
+    }
+    let lock = {
+        let mut locks = ctx.dav.locks.lock().unwrap();
+        let conflict = locks.iter().any(|v| {
+            (covers(v, key) || (infinite && is_below(&v.key, key))) && (exclusive || v.exclusive)
+        });
+        if conflict {
+            return Response::new("423 Locked");
+        }
+        let lock = Lock {
+            token: new_token(),
+            key: key.to_string(),
+            infinite,
+            exclusive,
+            owner,
+            timeout,
+            expires: Instant::now() + Duration::from_secs(timeout),
+        };
+        locks.push(lock.clone());
+        lock
+    };
+    // locking an unmapped URL creates an empty file
+    if created && ctx.fs.write_file(file_path, vec![]).await.is_err() {
+        ctx.dav
+            .locks
+            .lock()
+            .unwrap()
+            .retain(|v| v.token != lock.token);
+        return Response::new("500 INTERNAL SERVER ERROR");
+    }
+    let mut response = Response::ok(
+        "application/xml; charset=utf-8",
+        lock_body(ctx, &lock).into_bytes(),
+    )
+    .with_header("Lock-Token", format!("<{}>", lock.token));
+    if created {
+        response.status = "201 Created";
+    }
+    response
+}
+
+fn unlock(request: &Request, key: &str, ctx: &Context<impl FileSystemInterface>) -> Response {
+    let Some(token) = request.header("Lock-Token") else {
+        return Response::new("400 Bad Request");
+    };
+    let token = token.trim().trim_start_matches('<').trim_end_matches('>');
+    let mut locks = ctx.dav.locks.lock().unwrap();
+    match locks
+        .iter()
+        .position(|v| v.token == token && covers(v, key))
+    {
+        Some(i) => {
+            locks.remove(i);
+            Response::new("204 No Content")
+        }
+        None => Response::new("409 Conflict"),
+    }
+}
+
+impl Dav {
+    fn expire(&self) {
+        let now = Instant::now();
+        self.locks.lock().unwrap().retain(|v| v.expires > now);
+    }
+
+    /// `423 Locked` unless the request submits the tokens of all locks on `key`, on
+    /// its parent and, if `descendants` are affected too, below it.
+    fn check(&self, request: &Request, key: &str, descendants: bool) -> Option<Response> {
+        let tokens = submitted_tokens(request);
+        let locks = self.locks.lock().unwrap();
+        let relevant: Vec<_> = locks
+            .iter()
+            .filter(|v| {
+                covers(v, key)
+                    || parent(key) == Some(v.key.as_str())
+                    || (descendants && is_below(&v.key, key))
+            })
+            .collect();
+        // one token is enough for resources under several shared locks
+        let shared = relevant
+            .iter()
+            .any(|v| !v.exclusive && tokens.contains(&v.token));
+        let unlocked = relevant
+            .iter()
+            .all(|v| tokens.contains(&v.token) || (!v.exclusive && shared));
+        (!unlocked).then(|| Response::new("423 Locked"))
+    }
+
+    /// Drops the locks and properties of `key` and everything below it.
+    fn forget(&self, key: &str) {
+        let gone = |v: &str| v == key || is_below(v, key);
+        self.locks.lock().unwrap().retain(|v| !gone(&v.key));
+        self.properties.lock().unwrap().retain(|k, _| !gone(k));
+    }
+
+    /// Moves the properties of `from` and everything below it to `to`.
+    fn moved(&self, from: &str, to: &str) {
+        self.locks
+            .lock()
+            .unwrap()
+            .retain(|v| v.key != from && !is_below(&v.key, from));
+        let mut properties = self.properties.lock().unwrap();
+        let keys: Vec<_> = properties
+            .keys()
+            .filter(|k| *k == from || is_below(k, from))
+            .cloned()
+            .collect();
+        for key in keys {
+            if let Some(value) = properties.remove(&key) {
+                properties.insert(format!("{to}{}", &key[from.len()..]), value);
+            }
+        }
+    }
+
+    fn dead_properties(&self, key: &str) -> Vec<Property> {
+        let properties = self.properties.lock().unwrap();
+        properties.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Names and XML values of the `DAV:` properties of `key`.
+    fn live_properties(
+        &self,
+        ctx: &Context<impl FileSystemInterface>,
+        key: &str,
+        metadata: &Metadata,
+    ) -> Vec<(&'static str, String)> {
+        let name = key.rsplit('/').next().unwrap_or_default();
+        let mut properties = vec![("displayname", escape(name))];
+        match metadata.kind {
+            Kind::Dir => properties.push(("resourcetype", "<D:collection/>".to_string())),
+            Kind::File => {
+                let mime = mime_guess::from_path(name).first_or_octet_stream();
+                properties.push(("resourcetype", String::new()));
+                properties.push(("getcontentlength", metadata.len.to_string()));
+                properties.push(("getcontenttype", escape(mime.essence_str())));
+            }
+        }
+        properties.push(("getetag", escape(&etag(metadata))));
+        if let Some(modified) = metadata.modified {
+            properties.push(("getlastmodified", http_date(modified)));
+        }
+        properties.push(("supportedlock", SUPPORTED_LOCK.to_string()));
+        let active: String = self
+            .locks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|v| covers(v, key))
+            .map(|v| active_lock(ctx, v))
+            .collect();
+        properties.push(("lockdiscovery", active));
+        properties
+    }
+}
+
+const XML: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>";
+const SUPPORTED_LOCK: &str = concat!(
+    "<D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>",
+    "<D:lockentry><D:lockscope><D:shared/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>",
+);
+
+/// The parsed request body, `None` if it's empty.
+fn body(request: &Request) -> Result<Option<Element>, ()> {
+    let text = String::from_utf8_lossy(&request.body);
+    match text.trim().is_empty() {
+        true => Ok(None),
+        false => xml::parse(&text).map(Some).ok_or(()),
+    }
+}
+
+fn multistatus(body: String) -> Response {
+    let mut response = Response::ok("application/xml; charset=utf-8", body.into_bytes());
+    response.status = "207 Multi-Status";
+    response
+}
+
+fn propstat(props: &str, status: &str) -> String {
+    format!(
+        "<D:propstat><D:prop>{props}</D:prop><D:status>HTTP/1.1 {status}</D:status></D:propstat>"
+    )
+}
+
+fn empty_element(ns: &str, name: &str) -> String {
+    match ns {
+        DAV => format!("<D:{name}/>"),
+        ns => format!("<{name} xmlns=\"{}\"/>", escape(ns)),
+    }
+}
+
+fn dead_element(property: &Property) -> String {
+    format!(
+        "<{0} xmlns=\"{1}\">{2}</{0}>",
+        property.name,
+        escape(&property.ns),
+        escape(&property.value)
+    )
+}
+
+fn lock_body(ctx: &Context<impl FileSystemInterface>, lock: &Lock) -> String {
+    format!(
+        "{XML}<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery>{}</D:lockdiscovery></D:prop>",
+        active_lock(ctx, lock)
+    )
+}
+
+fn active_lock(ctx: &Context<impl FileSystemInterface>, lock: &Lock) -> String {
+    format!(
+        "<D:activelock><D:locktype><D:write/></D:locktype><D:lockscope>{}</D:lockscope><D:depth>{}</D:depth><D:owner>{}</D:owner><D:timeout>Second-{}</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken><D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock>",
+        if lock.exclusive { "<D:exclusive/>" } else { "<D:shared/>" },
+        if lock.infinite { "infinity" } else { "0" },
+        lock.owner,
+        lock.timeout,
+        lock.token,
+        escape(&encode_key(ctx, &lock.key)),
+    )
+}
+
+/// The URL of `key`, with a trailing `/` for collections.
+fn href(ctx: &Context<impl FileSystemInterface>, key: &str, metadata: &Metadata) -> String {
+    let mut href = encode_key(ctx, key);
+    if metadata.kind == Kind::Dir && !href.ends_with('/') {
+        href.push('/');
+    }
+    escape(&href)
+}
+
+fn encode_key(ctx: &Context<impl FileSystemInterface>, key: &str) -> String {
+    let segments: Vec<_> = key.split('/').map(percent_encode).collect();
+    format!("{}/{}", ctx.options.endpoint("dav"), segments.join("/"))
+}
+
+/// Decoded path below the WebDAV root, without empty, `.` and `..` segments.
+fn normalize(raw: &str) -> String {
+    let decoded = percent_decode(raw, false);
+    let segments: Vec<_> = decoded
+        .split(['/', '\\'])
+        .filter(|v| !matches!(*v, "" | "." | ".."))
+        .collect();
+    segments.join("/")
+}
+
+fn is_below(key: &str, ancestor: &str) -> bool {
+    match ancestor.is_empty() {
+        true => !key.is_empty(),
+        false => key
+            .strip_prefix(ancestor)
+            .is_some_and(|v| v.starts_with('/')),
+    }
+}
+
+fn parent(key: &str) -> Option<&str> {
+    match key.rsplit_once('/') {
+        Some((parent, _)) => Some(parent),
+        None => (!key.is_empty()).then_some(""),
+    }
+}
+
+/// Whether `lock` applies to `key` itself.
+fn covers(lock: &Lock, key: &str) -> bool {
+    lock.key == key || (lock.infinite && is_below(key, &lock.key))
+}
+
+/// Lock tokens in the `If` header.
+fn submitted_tokens(request: &Request) -> Vec<String> {
+    let Some(header) = request.header("If") else {
+        return vec![];
+    };
+    header
+        .split('<')
+        .filter_map(|v| v.split_once('>'))
+        .map(|(token, _)| token.to_string())
+        .filter(|v| v.starts_with("opaquelocktoken:"))
+        .collect()
+}
+
+fn new_token() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_nanos())
+        .unwrap_or_default();
+    // spread the bits of the time over the whole token
+    let id = (nanos ^ (NEXT_LOCK.fetch_add(1, Ordering::Relaxed) as u128) << 64)
+        .wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835);
+    format!(
+        "opaquelocktoken:{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        (id >> 96) as u32,
+        (id >> 80) as u16,
+        (id >> 64) as u16,
+        (id >> 48) as u16,
+        id as u64 & 0xffff_ffff_ffff
+    )
+}
+
+fn etag(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified
+        .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+        .map(|v| v.as_nanos())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", metadata.len, modified)
+}
+
+/// An RFC 1123 date like `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn http_date(time: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs() / 86400)
+        .unwrap_or_default();
+    let (year, month, day, secs) = listing::civil(time);
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        MONTHS[(month - 1) as usize],
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
@@ -8,11 +8,11 @@ use tokio::{
         tcp::{OwnedReadHalf, OwnedWriteHalf},
         TcpStream,
     },
-    sync::Mutex,
+    sync::{broadcast::error::RecvError, Mutex},
     task::JoinHandle,
 };
 
-use crate::Signal;
+use crate::{clients::Client, live, Context, FileSystemInterface};
 
 impl Opcode {
     fn from_byte(byte: u8) -> Self {
@@ -31,7 +31,7 @@ impl Opcode {
 #[derive(Debug)]
 pub struct WebSocketMessage {
     pub opcode: Opcode,
-    _payload: Vec<u8>,
+    pub payload: Vec<u8>,
 }
 
 #[derive(Debug)]
@@ -55,7 +55,7 @@ pub async fn read_websocket_message(
     stream.read_exact(&mut header).await?;
 
     // First byte: FIN and opcode
-    let fin = header[0] & 0x80 != 0;
+    let _fin = header[0] & 0x80 != 0;
     let opcode = Opcode::from_byte(header[0] & 0x0F);
 
     // Second byte: Mask and payload length
@@ -91,10 +91,7 @@ pub async fn read_websocket_message(
     }
 
     // Return the WebSocketMessage
-    Ok(WebSocketMessage {
-        opcode,
-        _payload: payload,
-    })
+    Ok(WebSocketMessage { opcode, payload })
 }
 
 /// Chatgpt
@@ -124,10 +121,11 @@ pub fn generate_websocket_accept_key(key: &str) -> String {
     BASE64_STANDARD.encode(result)
 }
 
-pub async fn handle_websocket(
+pub async fn handle_websocket<T: FileSystemInterface + 'static>(
     mut stream: TcpStream,
     key: String,
-    signal: Arc<Signal>,
+    ctx: Arc<Context<T>>,
+    client: Client,
 ) -> io::Result<()> {
     let response_key = generate_websocket_accept_key(&key);
     let response = format!(
@@ -139,13 +137,30 @@ pub async fn handle_websocket(
     );
     stream.write_all(response.as_bytes()).await?;
 
+    let registration = ctx.clients.register(client.clone());
     let (mut read_stream, mut write_stream) = stream.into_split();
+    let mut events = ctx.signal.subscribe();
+    // before the reader starts, which waits for the sender to be set
+    for msg in live::handshake(&ctx, &client) {
+        send_websocket_message(&mut write_stream, &msg).await?;
+    }
     let sender: Arc<Mutex<Option<JoinHandle<_>>>> = Arc::new(Mutex::new(None));
     let sender_read = sender.clone();
+    let read_ctx = ctx.clone();
+    let read_client = client.clone();
     let close = Arc::new(Mutex::new(tokio::spawn(async move {
         while let Ok(msg) = read_websocket_message(&mut read_stream).await {
-            if matches!(msg.opcode, Opcode::Close) {
-                break;
+            match msg.opcode {
+                Opcode::Close => break,
+                Opcode::Text => {
+                    live::receive(
+                        &read_ctx,
+                        &read_client,
+                        &String::from_utf8_lossy(&msg.payload),
+                    )
+                    .await;
+                }
+                _ => {}
             }
         }
         loop {
@@ -162,24 +177,20 @@ pub async fn handle_websocket(
         }
     })));
     *sender.lock().await = Some(tokio::spawn(async move {
+        let _registration = registration;
         loop {
-            let file_path = signal.wait_signal().await;
-            let css = file_path
-                .as_os_str()
-                .to_str()
-                .unwrap_or_default()
-                .trim()
-                .ends_with(".css");
-            let msg = if css {
-                send_websocket_message(
-                    &mut write_stream,
-                    &format!("update-css:///{}", file_path.display()),
-                )
-                .await
-            } else {
-                send_websocket_message(&mut write_stream, "reload").await
+            let event = match events.recv().await {
+                Ok(v) => v,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let Some(msg) = live::message(&ctx, &client, event).await else {
+                continue;
             };
-            if msg.is_err() {
+            if send_websocket_message(&mut write_stream, &msg)
+                .await
+                .is_err()
+            {
                 close.lock().await.abort();
                 break;
             }
@@ -0,0 +1,184 @@
+//! Just enough XML to read WebDAV request bodies.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Clone)]
+pub struct Element {
+    /// Namespace URI, e.g. `DAV:`.
+    pub ns: String,
+    pub name: String,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    pub fn is(&self, ns: &str, name: &str) -> bool {
+        self.ns == ns && self.name == name
+    }
+
+    pub fn child(&self, ns: &str, name: &str) -> Option<&Element> {
+        self.children.iter().find(|v| v.is(ns, name))
+    }
+}
+
+/// Parses `input` into its root element. Processing instructions, comments and
+/// doctypes are skipped, mixed content keeps only the text.
+pub fn parse(input: &str) -> Option<Element> {
+    let mut stack: Vec<(Element, HashMap<String, String>)> = vec![];
+    let mut rest = input;
+    while let Some(start) = rest.find('<') {
+        if let Some((element, _)) = stack.last_mut() {
+            element.text.push_str(&unescape(&rest[..start]));
+        }
+        rest = &rest[start..];
+        if let Some(v) = rest.strip_prefix("<?") {
+            rest = &v[v.find("?>")? + 2..];
+        } else if let Some(v) = rest.strip_prefix("<!--") {
+            rest = &v[v.find("-->")? + 3..];
+        } else if let Some(v) = rest.strip_prefix("<!") {
+            rest = &v[v.find('>')? + 1..];
+        } else if let Some(v) = rest.strip_prefix("</") {
+            rest = &v[v.find('>')? + 1..];
+            let (element, _) = stack.pop()?;
+            match stack.last_mut() {
+                Some((parent, _)) => parent.children.push(element),
+                None => return Some(element),
+            }
+        } else {
+            let end = rest.find('>')?;
+            let tag = &rest[1..end];
+            rest = &rest[end + 1..];
+            let (tag, closed) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let mut namespaces = stack.last().map(|(_, v)| v.clone()).unwrap_or_default();
+            let (qname, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+            for (key, value) in parse_attributes(attributes) {
+                if key == "xmlns" {
+                    namespaces.insert(String::new(), value);
+                } else if let Some(prefix) = key.strip_prefix("xmlns:") {
+                    namespaces.insert(prefix.to_string(), value);
+                }
+            }
+            let (prefix, name) = qname.split_once(':').unwrap_or(("", qname));
+            let element = Element {
+                ns: namespaces.get(prefix).cloned().unwrap_or_default(),
+                name: name.to_string(),
+                ..Default::default()
+            };
+            match (closed, stack.last_mut()) {
+                (true, Some((parent, _))) => parent.children.push(element),
+                (true, None) => return Some(element),
+                (false, _) => stack.push((element, namespaces)),
+            }
+        }
+    }
+    None
+}
+
+fn parse_attributes(mut input: &str) -> Vec<(String, String)> {
+    let mut attributes = vec![];
+    while let Some(eq) = input.find('=') {
+        let key = input[..eq].trim();
+        let value = input[eq + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|v| *v == '"' || *v == '\'') else {
+            break;
+        };
+        let Some(end) = value[1..].find(quote) else {
+            break;
+        };
+        attributes.push((key.to_string(), unescape(&value[1..end + 1])));
+        input = &value[end + 2..];
+    }
+    attributes
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => entity.strip_prefix('#').and_then(|v| v.parse().ok()),
+            }
+            .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_and_text() {
+        let input = r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- a comment -->
+<D:propertyupdate xmlns:D="DAV:" xmlns:x='urn:x'>
+  <D:set><D:prop><x:color>blue &amp; &#x67;reen &lt;3</x:color><plain/></D:prop></D:set>
+</D:propertyupdate>"#;
+        let root = parse(input).unwrap();
+        assert!(root.is("DAV:", "propertyupdate"));
+        let prop = root
+            .child("DAV:", "set")
+            .unwrap()
+            .child("DAV:", "prop")
+            .unwrap();
+        let color = prop.child("urn:x", "color").unwrap();
+        assert_eq!(color.text, "blue & green <3");
+        assert!(prop.child("", "plain").is_some());
+    }
+
+    #[test]
+    fn default_namespace_is_inherited() {
+        let root = parse(r#"<propfind xmlns="DAV:"><prop><getetag/></prop></propfind>"#).unwrap();
+        assert!(root.is("DAV:", "propfind"));
+        assert!(root
+            .child("DAV:", "prop")
+            .unwrap()
+            .child("DAV:", "getetag")
+            .is_some());
+        let root = parse("<a xmlns='urn:a'><b xmlns=''/></a>").unwrap();
+        assert!(root.child("", "b").is_some());
+    }
+
+    #[test]
+    fn self_closing_root_and_broken_input() {
+        assert!(parse("<D:allprop xmlns:D=\"DAV:\"/>")
+            .unwrap()
+            .is("DAV:", "allprop"));
+        assert!(parse("<a><b></b>").is_none());
+        assert!(parse("<a").is_none());
+        assert!(parse("text only").is_none());
+        assert!(parse("<!-- unterminated").is_none());
+        assert_eq!(
+            parse("<a>&bogus; &#xD800;</a>").unwrap().text,
+            "&bogus; &#xD800;"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! WebDAV requests against a served [`MemoryFileSystem`], sent with an HTTP client.
+#![cfg(feature = "webdav")]
+
+use std::time::Duration;
+
+use reqwest::{header::HeaderMap, Client, Method};
+use rusty_live_server::{serve_with_options, MemoryFileSystem, Options};
+use tokio::net::TcpStream;
+
+struct Response {
+    status: u16,
+    headers: HeaderMap,
+    body: String,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+}
+
+/// Serves `fs` with WebDAV on a free port and returns the port once it accepts.
+async fn start(fs: MemoryFileSystem) -> u16 {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let options = Options {
+        webdav: true,
+        ..Default::default()
+    };
+    tokio::spawn(serve_with_options(
+        "/site".into(),
+        port,
+        false,
+        None,
+        fs,
+        options,
+    ));
+    for _ in 0..100 {
+        if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return port;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the server didn't start");
+}
+
+async fn request(
+    port: u16,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> Response {
+    // the server closes the connection after every response
+    let client = Client::builder().pool_max_idle_per_host(0).build().unwrap();
+    let method = Method::from_bytes(method.as_bytes()).unwrap();
+    let url = format!("http://127.0.0.1:{port}/__live/dav{path}");
+    let mut request = client.request(method, url).body(body.to_string());
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = request.send().await.unwrap();
+    Response {
+        status: response.status().as_u16(),
+        headers: response.headers().clone(),
+        body: response.text().await.unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn propfind_lists_a_directory() {
+    let fs = MemoryFileSystem::new("/site");
+    fs.insert("index.html", "<h1>Hi</h1>").unwrap();
+    fs.insert("css/style.css", "body {}").unwrap();
+    let port = start(fs).await;
+
+    let infinite = request(port, "PROPFIND", "/", &[], "").await;
+    assert_eq!(infinite.status, 403);
+    assert!(infinite.body.contains("propfind-finite-depth"));
+
+    let response = request(port, "PROPFIND", "/", &[("Depth", "1")], "").await;
+    assert_eq!(response.status, 207);
+    assert!(response.body.contains("<D:href>/__live/dav/</D:href>"));
+    assert!(response.body.contains("<D:href>/__live/dav/css/</D:href>"));
+    assert!(response
+        .body
+        .contains("<D:href>/__live/dav/index.html</D:href>"));
+    assert!(response
+        .body
+        .contains("<D:getcontentlength>11</D:getcontentlength>"));
+
+    let body = r#"<?xml version="1.0"?><propfind xmlns="DAV:"><prop><getcontentlength/><x:color xmlns:x="urn:x"/></prop></propfind>"#;
+    let response = request(port, "PROPFIND", "/index.html", &[("Depth", "0")], body).await;
+    assert_eq!(response.status, 207);
+    assert!(response
+        .body
+        .contains("<D:getcontentlength>11</D:getcontentlength>"));
+    assert!(response.body.contains("404 Not Found"));
+
+    let missing = request(port, "PROPFIND", "/missing", &[("Depth", "0")], "").await;
+    assert_eq!(missing.status, 404);
+}
+
+#[tokio::test]
+async fn proppatch_stores_dead_properties() {
+    let fs = MemoryFileSystem::new("/site");
+    fs.insert("a.txt", "a").unwrap();
+    let port = start(fs).await;
+
+    let set = r#"<D:propertyupdate xmlns:D="DAV:" xmlns:x="urn:x"><D:set><D:prop><x:color>blue &amp; green</x:color></D:prop></D:set></D:propertyupdate>"#;
+    let response = request(port, "PROPPATCH", "/a.txt", &[], set).await;
+    assert_eq!(response.status, 207);
+    assert!(response.body.contains("200 OK"));
+
+    let find = r#"<D:propfind xmlns:D="DAV:"><D:prop><color xmlns="urn:x"/></D:prop></D:propfind>"#;
+    let response = request(port, "PROPFIND", "/a.txt", &[("Depth", "0")], find).await;
+    assert!(response.body.contains("blue &amp; green"));
+
+    // live properties are protected, which fails the whole update
+    let protected = r#"<D:propertyupdate xmlns:D="DAV:" xmlns:x="urn:x"><D:set><D:prop><D:getetag>x</D:getetag><x:size>1</x:size></D:prop></D:set></D:propertyupdate>"#;
+    let response = request(port, "PROPPATCH", "/a.txt", &[], protected).await;
+    assert!(response.body.contains("403 Forbidden"));
+    assert!(response.body.contains("424 Failed Dependency"));
+    let response = request(port, "PROPFIND", "/a.txt", &[("Depth", "0")], "").await;
+    assert!(!response.body.contains("size"));
+
+    let bad = request(port, "PROPPATCH", "/a.txt", &[], "<not-closed>").await;
+    assert_eq!(bad.status, 400);
+}
+
+#[tokio::test]
+async fn locks_guard_changes_until_unlocked() {
+    let fs = MemoryFileSystem::new("/site");
+    fs.insert("a.txt", "a").unwrap();
+    let port = start(fs).await;
+
+    let info = r#"<D:lockinfo xmlns:D="DAV:"><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype><D:owner><D:href>me</D:href></D:owner></D:lockinfo>"#;
+    let response = request(port, "LOCK", "/a.txt", &[("Timeout", "Second-60")], info).await;
+    assert_eq!(response.status, 200);
+    assert!(response.body.contains("<D:href>me</D:href>"));
+    let token = response.header("Lock-Token").unwrap().to_string();
+    let condition = format!("({token})");
+
+    let again = request(port, "LOCK", "/a.txt", &[], info).await;
+    assert_eq!(again.status, 423);
+    let put = request(port, "PUT", "/a.txt", &[], "b").await;
+    assert_eq!(put.status, 423);
+    let put = request(port, "PUT", "/a.txt", &[("If", &condition)], "b").await;
+    assert_eq!(put.status, 204);
+
+    let wrong = request(port, "UNLOCK", "/a.txt", &[("Lock-Token", "<x>")], "").await;
+    assert_eq!(wrong.status, 409);
+    let unlock = request(port, "UNLOCK", "/a.txt", &[("Lock-Token", &token)], "").await;
+    assert_eq!(unlock.status, 204);
+    let delete = request(port, "DELETE", "/a.txt", &[], "").await;
+    assert_eq!(delete.status, 204);
+
+    // locking an unmapped URL creates an empty file
+    let response = request(port, "LOCK", "/new.txt", &[], info).await;
+    assert_eq!(response.status, 201);
+    let response = request(port, "GET", "/new.txt", &[], "").await;
+    assert_eq!((response.status, response.body.as_str()), (200, ""));
+}
+
+#[tokio::test]
+async fn copy_and_move() {
+    let fs = MemoryFileSystem::new("/site");
+    fs.insert("docs/a.txt", "a").unwrap();
+    fs.insert("docs/sub/b.txt", "b").unwrap();
+    let port = start(fs).await;
+    let destination = format!("http://127.0.0.1:{port}/__live/dav/copy");
+
+    let copy = vec![("Destination", destination.as_str())];
+    let response = request(port, "COPY", "/docs", &copy, "").await;
+    assert_eq!(response.status, 201);
+    let response = request(port, "GET", "/copy/sub/b.txt", &[], "").await;
+    assert_eq!(response.body, "b");
+
+    let mut no_overwrite = copy.clone();
+    no_overwrite.push(("Overwrite", "F"));
+    let response = request(port, "COPY", "/docs", &no_overwrite, "").await;
+    assert_eq!(response.status, 412);
+    let response = request(port, "COPY", "/docs", &copy, "").await;
+    assert_eq!(response.status, 204);
+
+    let into_itself = [("Destination", "/__live/dav/docs/sub/docs")];
+    let response = request(port, "COPY", "/docs", &into_itself, "").await;
+    assert_eq!(response.status, 403);
+    for ancestor in ["/__live/dav/", "/__live/dav", "/__live/dav/docs"] {
+        let destination = [("Destination", ancestor)];
+        let response = request(port, "MOVE", "/docs/sub", &destination, "").await;
+        assert_eq!(response.status, 403);
+    }
+    let response = request(port, "GET", "/docs/sub/b.txt", &[], "").await;
+    assert_eq!(response.body, "b");
+    let missing_parent = [("Destination", "/__live/dav/none/a.txt")];
+    let response = request(port, "COPY", "/docs/a.txt", &missing_parent, "").await;
+    assert_eq!(response.status, 409);
+    let elsewhere = [("Destination", "/other/a.txt")];
+    let response = request(port, "COPY", "/docs/a.txt", &elsewhere, "").await;
+    assert_eq!(response.status, 502);
+
+    let moved = [("Destination", "/__live/dav/moved.txt")];
+    let response = request(port, "MOVE", "/docs/a.txt", &moved, "").await;
+    assert_eq!(response.status, 201);
+    let response = request(port, "GET", "/moved.txt", &[], "").await;
+    assert_eq!(response.body, "a");
+    let response = request(port, "GET", "/docs/a.txt", &[], "").await;
+    assert_eq!(response.status, 404);
+}
+
+#[tokio::test]
+async fn cross_site_changes_are_rejected() {
+    let port = start(MemoryFileSystem::new("/site")).await;
+    let origin = [("Origin", "https://evil.example")];
+    let response = request(port, "PUT", "/a.txt", &origin, "a").await;
+    assert_eq!(response.status, 403);
+    let response = request(port, "PROPFIND", "/", &[("Depth", "0")], "").await;
+    assert_eq!(response.status, 207);
+    let response = request(port, "GET", "/a.txt", &[], "").await;
+    assert_eq!(response.status, 404);
+}